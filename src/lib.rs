@@ -1,20 +1,37 @@
 #![allow(dead_code)]
 #![allow(unused_must_use)]
+#![allow(non_camel_case_types)]
 
 extern crate regex;
 extern crate rustc_serialize;
 extern crate bincode;
-
-use std::collections::{HashMap, HashSet};
+extern crate fs2;
+extern crate sha2;
+
+use std::collections::HashMap;
+#[cfg(test)]
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+#[cfg(test)]
+use std::io::{Read, Write};
+use std::path::PathBuf;
+#[cfg(test)]
+use std::sync::Arc;
+#[cfg(test)]
+use std::thread;
 
 mod db {
-	use std::path::Path;
+	use std::path::{Path, PathBuf};
+	use std::borrow::Cow;
+	use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+	use std::sync::atomic::{self, AtomicBool};
 	use std::collections::{HashMap, HashSet};
-	use std::fs;
-	use std::fs::{File, OpenOptions};
-	use std::io::{BufReader, ErrorKind};
-	use rustc_serialize::{Encodable, Decodable};
-	use bincode::SizeLimit;
+	use std::fs::File;
+	use std::io::Read;
+	use std::str::FromStr;
+	use std::fmt;
+	use std::cmp::Ordering;
 	use regex::Regex;
 
 	pub type DBResult<T> = Result<T, DatabaseError>;
@@ -28,13 +45,29 @@ mod db {
 		CouldNotOpenFile(String),
 		CouldNotCreateFile(String),
 		CouldNotCreateDirectory(String),
+		CouldNotRemoveFile(String),
 		ComponentNotDefined(String),
 		ResourceNotDefined(String),
 		InstanceNotDefined(String),
-		MalformedStructure(String)
+		DataAlreadyDefined(String),
+		ComponentNotWritable(String),
+		TransactionRolledBack(String),
+		DatabaseLocked(String),
+		ReadOnlyDatabase(String),
+		ComponentInUse(String),
+		AlreadyDefined(String),
+		MalformedStructure(String),
+		DataTypeMismatch(String),
+		MalformedData(String),
+		DataOutOfRange(String),
+		InstanceInUse(String),
+		IndexNotDefined(String),
+		DatabaseFailed(String),
+		IncompatibleVersion(String)
 	}
 
-	#[derive(RustcEncodable, RustcDecodable, Debug, PartialEq)]
+	/// Data compares by type first and by value within a type.
+	#[derive(RustcEncodable, RustcDecodable, Clone, Debug, PartialEq, PartialOrd)]
 	pub enum Data {
 		BOOL(bool),
 		CHAR(char),
@@ -48,7 +81,20 @@ mod db {
 		UNSIGNED_INT_64(u64),
 		FLOAT_32(f32),
 		FLOAT_64(f64),
-		STRING(String)
+		STRING(String),
+		/// No data. Components without data load as null, and saving null removes the data.
+		NULL,
+		DATE(Date),
+		TIME(Time),
+		TIMESTAMP(Timestamp),
+		/// A reference to a blob file. The data itself is read and written as a stream.
+		BLOB(Blob),
+		/// The elements of a list, in order.
+		LIST(Vec<Data>),
+		/// The elements of a set, sorted and without duplicates.
+		SET(Vec<Data>),
+		/// The id of an instance of the resource a reference component refers to.
+		REFERENCE(usize)
 	}
 
 	impl Data {
@@ -66,17 +112,435 @@ mod db {
 				&Data::UNSIGNED_INT_64(d) => Data::UNSIGNED_INT_64(d),
 				&Data::FLOAT_32(d) => Data::FLOAT_32(d),
 				&Data::FLOAT_64(d) => Data::FLOAT_64(d),
-				&Data::STRING(ref d) => Data::STRING(d.to_string())
+				&Data::STRING(ref d) => Data::STRING(d.to_string()),
+				&Data::NULL => Data::NULL,
+				&Data::DATE(d) => Data::DATE(d),
+				&Data::TIME(d) => Data::TIME(d),
+				&Data::TIMESTAMP(d) => Data::TIMESTAMP(d),
+				&Data::BLOB(ref d) => Data::BLOB(d.clone()),
+				&Data::LIST(ref d) => Data::LIST(d.clone()),
+				&Data::SET(ref d) => Data::SET(d.clone()),
+				&Data::REFERENCE(d) => Data::REFERENCE(d)
+			}
+		}
+
+		/// Returns the exact data type of the data, or `None` for null. Data of this type is
+		/// accepted by it, along with the wider types that share its representation. The type of
+		/// a collection is taken from its first element, so it is `None` for an empty one. Only
+		/// the component knows the resource a reference refers to, so it is `None` for references.
+		pub fn data_type(&self) -> Option<DataType> {
+			match self {
+				&Data::BOOL(_) => Some(DataType::BOOL),
+				&Data::CHAR(_) => Some(DataType::CHAR),
+				&Data::SIGNED_INT_8(_) => Some(DataType::SIGNED_INT_8),
+				&Data::SIGNED_INT_16(_) => Some(DataType::SIGNED_INT_16),
+				&Data::SIGNED_INT_32(_) => Some(DataType::SIGNED_INT_32),
+				&Data::SIGNED_INT_64(_) => Some(DataType::SIGNED_INT_64),
+				&Data::UNSIGNED_INT_8(_) => Some(DataType::UNSIGNED_INT_8),
+				&Data::UNSIGNED_INT_16(_) => Some(DataType::UNSIGNED_INT_16),
+				&Data::UNSIGNED_INT_32(_) => Some(DataType::UNSIGNED_INT_32),
+				&Data::UNSIGNED_INT_64(_) => Some(DataType::UNSIGNED_INT_64),
+				&Data::FLOAT_32(_) => Some(DataType::FLOAT_32),
+				&Data::FLOAT_64(_) => Some(DataType::FLOAT_64),
+				&Data::STRING(_) => Some(DataType::STRING),
+				&Data::NULL => None,
+				&Data::DATE(_) => Some(DataType::DATE),
+				&Data::TIME(_) => Some(DataType::TIME),
+				&Data::TIMESTAMP(_) => Some(DataType::TIMESTAMP),
+				&Data::BLOB(_) => Some(DataType::BLOB),
+				&Data::LIST(ref elements) => elements.first().and_then(|element| element.data_type()).map(|data_type| DataType::LIST(Box::new(data_type))),
+				&Data::SET(ref elements) => elements.first().and_then(|element| element.data_type()).map(|data_type| DataType::SET(Box::new(data_type))),
+				&Data::REFERENCE(_) => None
 			}
 		}
 	}
 
-	#[derive(RustcEncodable, RustcDecodable, Copy, Clone, Debug, PartialEq)]
+	/// The type of data a component holds. New types are only ever added at the end, since the
+	/// position of a type is what is stored in the database files.
+	#[derive(RustcEncodable, RustcDecodable, Clone, Debug, PartialEq)]
 	pub enum DataType {
+		/// An unsigned integer of any width.
 		UINT,
+		/// A signed integer of any width.
 		INT,
 		STRING,
-		PASSWORD
+		/// A string that is never shown back to the user.
+		PASSWORD,
+		BOOL,
+		CHAR,
+		SIGNED_INT_8,
+		SIGNED_INT_16,
+		SIGNED_INT_32,
+		SIGNED_INT_64,
+		UNSIGNED_INT_8,
+		UNSIGNED_INT_16,
+		UNSIGNED_INT_32,
+		UNSIGNED_INT_64,
+		FLOAT_32,
+		FLOAT_64,
+		DATE,
+		/// A time of day in UTC.
+		TIME,
+		/// A point in time in UTC.
+		TIMESTAMP,
+		/// Binary data of any size, stored in its own file.
+		BLOB,
+		/// Any number of elements of a type, in the order they were added.
+		LIST(Box<DataType>),
+		/// Distinct elements of a type.
+		SET(Box<DataType>),
+		/// An instance of a resource, and what happens to the reference when that instance is deleted.
+		REFERENCE(String, OnDelete)
+	}
+
+	impl DataType {
+		/// Returns whether data can be stored in a component of this type. Fixed width types only
+		/// accept data of exactly that width, so a value never changes width on its way through
+		/// the database. Null is accepted by every type, but is never an element of a collection.
+		pub fn accepts(&self, data: &Data) -> bool {
+			match (self, data) {
+				(_, &Data::NULL) => true,
+				(&DataType::UINT, &Data::UNSIGNED_INT_8(_)) |
+				(&DataType::UINT, &Data::UNSIGNED_INT_16(_)) |
+				(&DataType::UINT, &Data::UNSIGNED_INT_32(_)) |
+				(&DataType::UINT, &Data::UNSIGNED_INT_64(_)) => true,
+				(&DataType::INT, &Data::SIGNED_INT_8(_)) |
+				(&DataType::INT, &Data::SIGNED_INT_16(_)) |
+				(&DataType::INT, &Data::SIGNED_INT_32(_)) |
+				(&DataType::INT, &Data::SIGNED_INT_64(_)) => true,
+				(&DataType::PASSWORD, &Data::STRING(_)) => true,
+				(&DataType::REFERENCE(..), &Data::REFERENCE(_)) => true,
+				(&DataType::LIST(ref element_type), &Data::LIST(ref elements)) |
+				(&DataType::SET(ref element_type), &Data::SET(ref elements)) => {
+					elements.iter().all(|element| *element != Data::NULL && element_type.accepts(element))
+				},
+				(data_type, data) => Some(data_type.clone()) == data.data_type()
+			}
+		}
+
+		/// Returns the type of the elements of a list or set.
+		pub fn element_type(&self) -> Option<&DataType> {
+			match self {
+				&DataType::LIST(ref element_type) | &DataType::SET(ref element_type) => Some(element_type),
+				_ => None
+			}
+		}
+
+		/// Returns the resource referred to by a reference component, or by the elements of a list
+		/// or set component, along with what happens when a referred instance is deleted.
+		pub fn referred_resource(&self) -> Option<(&str, OnDelete)> {
+			match self {
+				&DataType::REFERENCE(ref resource_name, on_delete) => Some((resource_name, on_delete)),
+				&DataType::LIST(ref element_type) | &DataType::SET(ref element_type) => element_type.referred_resource(),
+				_ => None
+			}
+		}
+
+		/// Brings data into the form this type stores it in. Sets are sorted and lose the elements
+		/// that equal another by value, anything else is kept as it is.
+		fn normalize(&self, data: Data) -> Data {
+			match (self, data) {
+				(&DataType::SET(_), Data::SET(mut elements)) => {
+					elements.sort_by(|a, b| index_value(a).partial_cmp(&index_value(b)).unwrap_or(Ordering::Equal));
+					elements.dedup_by(|a, b| index_value(a) == index_value(b));
+					Data::SET(elements)
+				},
+				(_, data) => data
+			}
+		}
+	}
+
+	/// What happens to the references to an instance when it is deleted.
+	#[derive(RustcEncodable, RustcDecodable, Copy, Clone, Debug, PartialEq)]
+	pub enum OnDelete {
+		/// The instance cannot be deleted while it is referred to.
+		RESTRICT,
+		/// The instances referring to it are deleted along with it.
+		CASCADE,
+		/// The references are removed, leaving null or a collection without them.
+		SET_NULL
+	}
+
+	/// How the secondary index of a component finds instances by value.
+	#[derive(RustcEncodable, RustcDecodable, Copy, Clone, Debug, PartialEq)]
+	pub enum IndexType {
+		/// Finds the instances holding a value.
+		HASH,
+		/// Finds the instances holding a value, or a value in a range.
+		ORDERED
+	}
+
+	/// A calendar date between the years 0 and 9999.
+	#[derive(RustcEncodable, RustcDecodable, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+	pub struct Date {
+		year: u16,
+		month: u8,
+		day: u8
+	}
+
+	impl Date {
+		pub fn new(year: u16, month: u8, day: u8) -> DBResult<Date> {
+			if year > 9999 || month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+				return Err(DatabaseError::DataOutOfRange(format!("Date is out of range: {:04}-{:02}-{:02}", year, month, day)));
+			}
+
+			Ok(Date { year: year, month: month, day: day })
+		}
+
+		/// Parses an ISO-8601 date such as "2015-06-30".
+		pub fn parse(value: &str) -> DBResult<Date> {
+			let parts: Vec<&str> = value.split('-').collect();
+			if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+				return Err(malformed_data(value, &DataType::DATE));
+			}
+
+			Date::new(try!(parse_digits(parts[0], value, &DataType::DATE)) as u16, try!(parse_digits(parts[1], value, &DataType::DATE)) as u8, try!(parse_digits(parts[2], value, &DataType::DATE)) as u8)
+		}
+
+		pub fn year(&self) -> u16 { self.year }
+		pub fn month(&self) -> u8 { self.month }
+		pub fn day(&self) -> u8 { self.day }
+
+		/// Returns the number of days since 1970-01-01.
+		fn days_since_epoch(&self) -> i64 {
+			// shift the year to start in March, so the leap day is the last day of the year
+			let (year, month, day) = (self.year as i64, self.month as i64, self.day as i64);
+			let year = if month <= 2 { year - 1 } else { year };
+			let era = (if year >= 0 { year } else { year - 399 }) / 400;
+			let year_of_era = year - era * 400;
+			let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+			let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+			era * 146097 + day_of_era - 719468
+		}
+
+		fn from_days_since_epoch(days: i64) -> Date {
+			let days = days + 719468;
+			let era = (if days >= 0 { days } else { days - 146096 }) / 146097;
+			let day_of_era = days - era * 146097;
+			let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+			let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+			let shifted_month = (5 * day_of_year + 2) / 153;
+			let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+			let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+			let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+			Date { year: year as u16, month: month as u8, day: day as u8 }
+		}
+	}
+
+	impl fmt::Display for Date {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+		}
+	}
+
+	/// A time of day in UTC, precise to the nanosecond.
+	#[derive(RustcEncodable, RustcDecodable, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+	pub struct Time {
+		hour: u8,
+		minute: u8,
+		second: u8,
+		nanosecond: u32
+	}
+
+	impl Time {
+		pub fn new(hour: u8, minute: u8, second: u8, nanosecond: u32) -> DBResult<Time> {
+			if hour > 23 || minute > 59 || second > 59 || nanosecond > 999_999_999 {
+				return Err(DatabaseError::DataOutOfRange(format!("Time is out of range: {:02}:{:02}:{:02}.{:09}", hour, minute, second, nanosecond)));
+			}
+
+			Ok(Time { hour: hour, minute: minute, second: second, nanosecond: nanosecond })
+		}
+
+		/// Parses an ISO-8601 time such as "13:45", "13:45:30" or "13:45:30.250Z". A time is always
+		/// in UTC, so the only zone accepted is "Z".
+		pub fn parse(value: &str) -> DBResult<Time> {
+			let time = if value.ends_with('Z') { &value[..value.len() - 1] } else { value };
+			let (time, nanosecond) = try!(split_fraction(time, value, &DataType::TIME));
+			let parts: Vec<&str> = time.split(':').collect();
+			if parts.len() < 2 || parts.len() > 3 || parts.iter().any(|part| part.len() != 2) {
+				return Err(malformed_data(value, &DataType::TIME));
+			}
+			let second = if parts.len() == 3 { try!(parse_digits(parts[2], value, &DataType::TIME)) } else { 0 };
+
+			Time::new(try!(parse_digits(parts[0], value, &DataType::TIME)) as u8, try!(parse_digits(parts[1], value, &DataType::TIME)) as u8, second as u8, nanosecond)
+		}
+
+		pub fn hour(&self) -> u8 { self.hour }
+		pub fn minute(&self) -> u8 { self.minute }
+		pub fn second(&self) -> u8 { self.second }
+		pub fn nanosecond(&self) -> u32 { self.nanosecond }
+
+		fn seconds_since_midnight(&self) -> i64 {
+			self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+		}
+	}
+
+	impl fmt::Display for Time {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			try!(write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second));
+			write_fraction(f, self.nanosecond)
+		}
+	}
+
+	/// A point in time in UTC, stored as the time since 1970-01-01T00:00:00Z and precise to the
+	/// nanosecond. Timestamps fall within the years 0 to 9999, like dates.
+	#[derive(RustcEncodable, RustcDecodable, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+	pub struct Timestamp {
+		seconds: i64,
+		nanoseconds: u32
+	}
+
+	impl Timestamp {
+		pub fn new(date: Date, time: Time) -> Timestamp {
+			Timestamp {
+				seconds: date.days_since_epoch() * 86400 + time.seconds_since_midnight(),
+				nanoseconds: time.nanosecond
+			}
+		}
+
+		pub fn from_unix(seconds: i64, nanoseconds: u32) -> DBResult<Timestamp> {
+			let first = Date { year: 0, month: 1, day: 1 }.days_since_epoch() * 86400;
+			let last = Date { year: 9999, month: 12, day: 31 }.days_since_epoch() * 86400 + 86399;
+			if seconds < first || seconds > last || nanoseconds > 999_999_999 {
+				return Err(DatabaseError::DataOutOfRange(format!("Timestamp is out of range: {}.{:09}", seconds, nanoseconds)));
+			}
+
+			Ok(Timestamp { seconds: seconds, nanoseconds: nanoseconds })
+		}
+
+		/// Parses an ISO-8601 date and time such as "2015-06-30T13:45:30.250Z". The date and time
+		/// may also be separated by a space. A time with an offset such as "+02:00" is converted to
+		/// UTC, and a time without a zone is taken to be UTC.
+		pub fn parse(value: &str) -> DBResult<Timestamp> {
+			if !value.bytes().all(|b| b < 128) || value.len() < 16 {
+				return Err(malformed_data(value, &DataType::TIMESTAMP));
+			}
+			let (date, separator, time) = (&value[..10], &value[10..11], &value[11..]);
+			if separator != "T" && separator != " " {
+				return Err(malformed_data(value, &DataType::TIMESTAMP));
+			}
+
+			// split off the zone, the time itself never contains a sign
+			let (time, offset) = if time.ends_with('Z') {
+				(&time[..time.len() - 1], 0)
+			} else {
+				match time.find(|c| c == '+' || c == '-') {
+					Some(index) => (&time[..index], try!(parse_offset(&time[index..], value))),
+					None => (time, 0)
+				}
+			};
+
+			let date = try!(Date::parse(date).map_err(|_| malformed_data(value, &DataType::TIMESTAMP)));
+			let time = try!(Time::parse(time).map_err(|_| malformed_data(value, &DataType::TIMESTAMP)));
+			let timestamp = Timestamp::new(date, time);
+			Timestamp::from_unix(timestamp.seconds - offset, timestamp.nanoseconds)
+		}
+
+		/// Returns the seconds since 1970-01-01T00:00:00Z, negative for earlier timestamps.
+		pub fn seconds(&self) -> i64 { self.seconds }
+		pub fn nanoseconds(&self) -> u32 { self.nanoseconds }
+
+		pub fn date(&self) -> Date {
+			Date::from_days_since_epoch(self.days_since_epoch())
+		}
+
+		pub fn time(&self) -> Time {
+			let seconds = self.seconds - self.days_since_epoch() * 86400;
+			Time {
+				hour: (seconds / 3600) as u8,
+				minute: (seconds / 60 % 60) as u8,
+				second: (seconds % 60) as u8,
+				nanosecond: self.nanoseconds
+			}
+		}
+
+		/// Returns the days since 1970-01-01, rounding down for earlier timestamps.
+		fn days_since_epoch(&self) -> i64 {
+			if self.seconds >= 0 { self.seconds / 86400 } else { (self.seconds - 86399) / 86400 }
+		}
+	}
+
+	impl fmt::Display for Timestamp {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			write!(f, "{}T{}Z", self.date(), self.time())
+		}
+	}
+
+	fn days_in_month(year: u16, month: u8) -> u8 {
+		match month {
+			2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+			2 => 28,
+			4 | 6 | 9 | 11 => 30,
+			_ => 31
+		}
+	}
+
+	/// Parses a part of a date or time, which must be nothing but digits.
+	fn parse_digits(part: &str, value: &str, data_type: &DataType) -> DBResult<u32> {
+		if part.is_empty() || !part.chars().all(|c| c.is_digit(10)) {
+			return Err(malformed_data(value, data_type));
+		}
+
+		part.parse::<u32>().map_err(|_| malformed_data(value, data_type))
+	}
+
+	/// Splits the fraction of a second off a time, returning it as nanoseconds.
+	fn split_fraction<'a>(time: &'a str, value: &str, data_type: &DataType) -> DBResult<(&'a str, u32)> {
+		match time.find('.') {
+			Some(index) => {
+				let fraction = &time[index + 1..];
+				if fraction.len() > 9 {
+					return Err(malformed_data(value, data_type));
+				}
+				let digits = try!(parse_digits(fraction, value, data_type));
+				Ok((&time[..index], digits * 10u32.pow(9 - fraction.len() as u32)))
+			},
+			None => Ok((time, 0))
+		}
+	}
+
+	/// Parses a zone offset such as "+02:00" or "-0530" into seconds.
+	fn parse_offset(offset: &str, value: &str) -> DBResult<i64> {
+		let digits = offset[1..].replace(":", "");
+		if digits.len() != 4 {
+			return Err(malformed_data(value, &DataType::TIMESTAMP));
+		}
+		let hours = try!(parse_digits(&digits[..2], value, &DataType::TIMESTAMP)) as i64;
+		let minutes = try!(parse_digits(&digits[2..], value, &DataType::TIMESTAMP)) as i64;
+		if hours > 23 || minutes > 59 {
+			return Err(out_of_range_data(value, &DataType::TIMESTAMP));
+		}
+
+		let seconds = hours * 3600 + minutes * 60;
+		Ok(if offset.starts_with('-') { -seconds } else { seconds })
+	}
+
+	/// Writes the fraction of a second without trailing zeros, or nothing for whole seconds.
+	fn write_fraction(f: &mut fmt::Formatter, nanoseconds: u32) -> fmt::Result {
+		if nanoseconds == 0 {
+			return Ok(());
+		}
+
+		let fraction = format!("{:09}", nanoseconds);
+		write!(f, ".{}", fraction.trim_end_matches('0'))
+	}
+
+	/// A blob stored in the database directory. Blobs are addressed by the SHA-256 hash of their
+	/// content, so identical data is only stored once. A blob is only created by writing its data
+	/// with `Database::write_blob`.
+	#[derive(RustcEncodable, RustcDecodable, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+	pub struct Blob {
+		hash: String,
+		length: u64
+	}
+
+	impl Blob {
+		/// Returns the SHA-256 hash of the data, in hex.
+		pub fn hash(&self) -> &str { &self.hash }
+		/// Returns the length of the data in bytes.
+		pub fn len(&self) -> u64 { self.length }
 	}
 
 	#[derive(RustcEncodable, RustcDecodable, Copy, Clone, Debug, PartialEq)]
@@ -100,8 +564,8 @@ mod db {
 	#[derive(RustcEncodable, RustcDecodable, Copy, Clone, Debug, PartialEq)]
 	pub enum ResourceIO {
 		/// A form type can create instances. This would be for resources like blog posts.
-		/// Each form resource must have an associated instance id. This is usually in the
-		/// form of an auto-generated number id, but can be overridden.
+		/// Each form resource owns any number of instances, each with its own instance id.
+		/// This is usually in the form of an auto-generated number id, but can be overridden.
 		FORM,
 		/// A static page has no instances. It is a single page that does not handle data
 		/// submission. An example would be a login page. Any static resource that submits
@@ -126,6 +590,106 @@ mod db {
 		pub fn safe(self) -> String {
 		    (self.filter)(self.data)
 		}
+
+		/// Moves Tainted out of scope and parses the safe String into data of a type. Surrounding
+		/// whitespace is ignored for every type but strings, and blank input is null. Returns
+		/// `MalformedData` for input that is not of the type and `DataOutOfRange` for numbers that
+		/// do not fit its width.
+		pub fn parse(self, data_type: DataType) -> DBResult<Data> {
+			let data_type = &data_type;
+			let safe = self.safe();
+			let value = safe.trim();
+			match *data_type {
+				DataType::STRING | DataType::PASSWORD => return Ok(Data::STRING(safe.to_string())),
+				_ if value.is_empty() => return Ok(Data::NULL),
+				_ => ()
+			};
+
+			match *data_type {
+				DataType::BOOL => {
+					match &value.to_lowercase()[..] {
+						"true" | "1" | "on" | "yes" => Ok(Data::BOOL(true)),
+						"false" | "0" | "off" | "no" => Ok(Data::BOOL(false)),
+						_ => Err(malformed_data(value, data_type))
+					}
+				},
+				DataType::CHAR => {
+					let mut chars = value.chars();
+					match (chars.next(), chars.next()) {
+						(Some(c), None) => Ok(Data::CHAR(c)),
+						_ => Err(malformed_data(value, data_type))
+					}
+				},
+				DataType::SIGNED_INT_8 => parse_signed(value, data_type).map(Data::SIGNED_INT_8),
+				DataType::SIGNED_INT_16 => parse_signed(value, data_type).map(Data::SIGNED_INT_16),
+				DataType::SIGNED_INT_32 => parse_signed(value, data_type).map(Data::SIGNED_INT_32),
+				DataType::SIGNED_INT_64 | DataType::INT => parse_signed(value, data_type).map(Data::SIGNED_INT_64),
+				DataType::UNSIGNED_INT_8 => parse_unsigned(value, data_type).map(Data::UNSIGNED_INT_8),
+				DataType::UNSIGNED_INT_16 => parse_unsigned(value, data_type).map(Data::UNSIGNED_INT_16),
+				DataType::UNSIGNED_INT_32 => parse_unsigned(value, data_type).map(Data::UNSIGNED_INT_32),
+				DataType::UNSIGNED_INT_64 | DataType::UINT => parse_unsigned(value, data_type).map(Data::UNSIGNED_INT_64),
+				DataType::FLOAT_32 => {
+					let float = try!(parse_float(value, data_type));
+					if (float as f32).is_infinite() {
+						return Err(out_of_range_data(value, data_type));
+					}
+					Ok(Data::FLOAT_32(value.parse::<f32>().unwrap()))
+				},
+				DataType::FLOAT_64 => parse_float(value, data_type).map(Data::FLOAT_64),
+				DataType::DATE => Date::parse(value).map(Data::DATE),
+				DataType::TIME => Time::parse(value).map(Data::TIME),
+				DataType::TIMESTAMP => Timestamp::parse(value).map(Data::TIMESTAMP),
+				// blobs never come from a string, they are streamed in with Database::write_blob
+				DataType::BLOB => Err(malformed_data(value, data_type)),
+				// collections are built one element at a time
+				DataType::LIST(_) | DataType::SET(_) => Err(malformed_data(value, data_type)),
+				DataType::REFERENCE(..) => parse_unsigned(value, data_type).map(Data::REFERENCE),
+				DataType::STRING | DataType::PASSWORD => unreachable!()
+			}
+		}
+	}
+
+	/// Parses a signed integer. Input that has the form of an integer but does not parse can
+	/// only be too large for the width.
+	fn parse_signed<T: FromStr>(value: &str, data_type: &DataType) -> DBResult<T> {
+		let digits = value.trim_start_matches(|c| c == '-' || c == '+');
+		if digits.is_empty() || value.len() - digits.len() > 1 || !digits.chars().all(|c| c.is_digit(10)) {
+			return Err(malformed_data(value, data_type));
+		}
+
+		value.parse::<T>().map_err(|_| out_of_range_data(value, data_type))
+	}
+
+	/// Parses an unsigned integer. Negative integers are out of range, except for zero.
+	fn parse_unsigned<T: FromStr>(value: &str, data_type: &DataType) -> DBResult<T> {
+		let digits = value.trim_start_matches(|c| c == '-' || c == '+');
+		if digits.is_empty() || value.len() - digits.len() > 1 || !digits.chars().all(|c| c.is_digit(10)) {
+			return Err(malformed_data(value, data_type));
+		}
+		if value.starts_with('-') && digits.chars().any(|c| c != '0') {
+			return Err(out_of_range_data(value, data_type));
+		}
+
+		digits.parse::<T>().map_err(|_| out_of_range_data(value, data_type))
+	}
+
+	/// Parses a finite float. Infinity and NaN are not accepted as input, while numbers too large
+	/// to be finite are out of range.
+	fn parse_float(value: &str, data_type: &DataType) -> DBResult<f64> {
+		let numeric = value.chars().all(|c| c.is_digit(10) || "+-.eE".contains(c));
+		match value.parse::<f64>() {
+			Ok(float) if float.is_finite() => Ok(float),
+			Ok(_) if numeric => Err(out_of_range_data(value, data_type)),
+			_ => Err(malformed_data(value, data_type))
+		}
+	}
+
+	fn malformed_data(value: &str, data_type: &DataType) -> DatabaseError {
+		DatabaseError::MalformedData(format!("Value is not {:?}: {}", data_type, value))
+	}
+
+	fn out_of_range_data(value: &str, data_type: &DataType) -> DatabaseError {
+		DatabaseError::DataOutOfRange(format!("Value is out of range for {:?}: {}", data_type, value))
 	}
 
 	pub type Filter = fn(String) -> String;
@@ -150,685 +714,5558 @@ mod db {
 		pub data: Data
 	}
 
-	// Admin functions
+	/// Describes a defined resource.
+	#[derive(Debug, PartialEq)]
+	pub struct ResourceInfo {
+		pub resource_name: String,
+		pub resource_io_type: ResourceIO,
+		pub instance_ids: Vec<usize>
+	}
 
-	pub fn reset() -> DBResult<()> {
-		try!(internals::start_from_scratch());
-		Ok(())
+	/// Describes a defined component.
+	#[derive(Debug, PartialEq)]
+	pub struct ComponentInfo {
+		pub component_name: String,
+		pub component_data_type: DataType
 	}
 
-	pub fn add_resource(resource_name: &str, resource_type: ResourceIO,	instance_id: Option<usize>) -> DBResult<()> {
-		let mut resources = try!(internals::Resources::load());
+	/// A component along with every resource whose model has it, sorted by name.
+	#[derive(Debug, PartialEq)]
+	pub struct SharedComponent {
+		pub component_name: String,
+		pub resource_names: Vec<String>
+	}
 
-		// add resource definition
-		let resource_id = resources.next_resource_id;
-		resources.next_resource_id = resources.next_resource_id + 1;
-		resources.resources.insert(resource_name.to_string(), (resource_id, resource_type));
-		if instance_id != None {
-			// to have an instance id, the resource must be FORM IO
-			if resource_type != ResourceIO::FORM {
-				return Err(DatabaseError::MalformedStructure(format!("Resource is not FORM IO type: {}", resource_name)));
-			}
-			resources.resource_instances.insert(resource_id, instance_id.unwrap());
-		}
+	/// An instance of a resource.
+	#[derive(Debug, PartialEq)]
+	pub struct ResourceInstance {
+		pub resource_name: String,
+		pub instance_id: usize
+	}
 
-		// add model definition
-		resources.models.insert(resource_id, HashMap::new());
+	/// An instance reached by following references, with the reference component that led to it
+	/// and the number of references followed from the first instance.
+	#[derive(Debug, PartialEq)]
+	pub struct ReachedInstance {
+		pub resource_name: String,
+		pub instance_id: usize,
+		pub component_name: String,
+		pub depth: usize
+	}
 
-		// save changes
-		try!(resources.save());
+	/// Which way references are followed.
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	pub enum Direction {
+		/// From an instance to the instances its reference components refer to.
+		OUTGOING,
+		/// From an instance to the instances that refer to it.
+		INCOMING
+	}
 
-		Ok(())
+	/// A request path resolved to the resource that handles it. Paths that match a route pattern
+	/// such as "/blog/{username}/{slug}/" carry the captured parameters and the instance they
+	/// belong to, if there is one yet.
+	#[derive(Debug, PartialEq)]
+	pub struct Route {
+		pub resource_name: String,
+		pub instance_id: Option<usize>,
+		pub params: HashMap<String, String>
 	}
 
-	pub fn add_component(component_name: &str, data_type: DataType) -> DBResult<()> {
-		let mut components = try!(internals::Components::load());
+	/// A database stored in its own directory, so a process can work with several databases at
+	/// once. The decoded database files are kept in memory; reads never touch the disk and every
+	/// committed change is written through to the files before it becomes visible.
+	///
+	/// A database can be shared between threads through an `Arc`. Reads run concurrently, while
+	/// transactions (and so every write) run one at a time, each seeing the changes committed by
+	/// the ones before it.
+	///
+	/// Other processes are kept out of the directory by an advisory lock that is held until the
+	/// database is dropped. A database opened for writing holds it exclusively, while any number
+	/// of read only databases can share it.
+	pub struct Database {
+		path: PathBuf,
+		state: RwLock<State>,
+		writer: Mutex<()>, // held by the open transaction
+		read_only: bool,
+		failed: AtomicBool, // set when a commit fails part way through writing the files
+		_lock: File // the lock is released when the file is closed
+	}
 
-		// add component definition
-		let component_id = components.next_component_id;
-		components.next_component_id = components.next_component_id + 1;
-		components.components.insert(component_name.to_string(), component_id);
-		components.component_names.insert(component_id, component_name.to_string());
+	impl Database {
+		/// Creates a new, empty database in the given directory. The directory is created if it
+		/// does not exist yet. A database already in it is never replaced, use `reset` for that.
+		pub fn create<P: AsRef<Path>>(path: P) -> DBResult<Database> {
+			let path = path.as_ref().to_path_buf();
+			try!(internals::create_directory(&path));
+			let lock = try!(internals::lock_directory(&path, true));
+			if internals::database_exists(&path) {
+				return Err(DatabaseError::AlreadyDefined(format!("Database already exists: {}", path.display())));
+			}
+			try!(internals::start_from_scratch(&path));
+			let state = try!(State::load(&path));
+
+			Ok(Database {
+				path: path,
+				state: RwLock::new(state),
+				writer: Mutex::new(()),
+				read_only: false,
+				failed: AtomicBool::new(false),
+				_lock: lock
+			})
+		}
 
-		// add type
-		components.component_data_types.insert(component_id, data_type);
+		/// Opens an existing database in the given directory.
+		pub fn open<P: AsRef<Path>>(path: P) -> DBResult<Database> {
+			let path = path.as_ref().to_path_buf();
+			let lock = try!(internals::lock_directory(&path, true));
+			try!(internals::check_version(&path));
 
-		// save changes
-		try!(components.save());
+			// clean up after any save that was interrupted
+			try!(internals::recover(&path));
 
-		Ok(())
-	}
+			// all database files must be present and readable
+			let mut state = try!(State::load(&path));
+			if state.indexes_changed {
+				try!(state.indexes.save(&path));
+				state.indexes_changed = false;
+			}
 
-	pub fn add_component_to_model(resource_name: &str, component_name: &str, io_type: DataIO) -> DBResult<()> {
-		let mut resources = try!(internals::Resources::load());
-		let components = try!(internals::Components::load());
-		let mut instances = try!(internals::Instances::load());
-		
-		// the resource must exist
-		if !resources.resources.contains_key(resource_name) {
-			return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)));
+			Ok(Database {
+				path: path,
+				state: RwLock::new(state),
+				writer: Mutex::new(()),
+				read_only: false,
+				failed: AtomicBool::new(false),
+				_lock: lock
+			})
 		}
 
-		// the component must exist
-		if !components.components.contains_key(component_name) {
-			return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)));
-		}
+		/// Opens an existing database in the given directory for reading only. Every write
+		/// returns `ReadOnlyDatabase`.
+		pub fn open_read_only<P: AsRef<Path>>(path: P) -> DBResult<Database> {
+			let path = path.as_ref().to_path_buf();
+			let lock = try!(internals::lock_directory(&path, false));
+			try!(internals::check_version(&path));
+
+			// all database files must be present and readable
+			let mut state = try!(State::load(&path));
 
-		let component_id = components.components.get(component_name).unwrap();
-		let resource_id = resources.resources.get(resource_name).unwrap().0;
-		{ // mutable scope for adding component id to resources
-			let mut model = resources.models.get_mut(&resource_id).unwrap();
-			model.insert(*component_id, io_type);
+			// a read only database cannot replay the log into the files, so replay it in memory
+			for entry in try!(internals::read_log(&path)) {
+				if let Some(resources) = entry.resources {
+					state.resources = Arc::new(resources.into_owned());
+				}
+				if let Some(components) = entry.components {
+					state.components = Arc::new(components.into_owned());
+				}
+				if let Some(instances) = entry.instances {
+					state.instances = Arc::new(instances.into_owned());
+				}
+				if let Some(indexes) = entry.indexes {
+					state.indexes = Arc::new(indexes.into_owned());
+				}
+			}
+
+			Ok(Database {
+				path: path,
+				state: RwLock::new(state),
+				writer: Mutex::new(()),
+				read_only: true,
+				failed: AtomicBool::new(false),
+				_lock: lock
+			})
 		}
 
-		// add component instance to instances (if not static)
-		if io_type != DataIO::STATIC && !instances.instances.contains_key(&component_id) {
-			// if no instances defined yet for component, then insert a new one
-			instances.instances.insert(*component_id, HashMap::new());
-			try!(instances.save());		
+		/// Returns the directory that holds the database files.
+		pub fn path(&self) -> &Path {
+			&self.path
 		}
 
-		// save changes
-		try!(resources.save());
+		// Admin functions
 
-		Ok(())
-	}
+		pub fn reset(&self) -> DBResult<()> {
+			try!(self.check_writable());
+			let _writer = self.lock_writer();
+			if let Err(error) = internals::start_from_scratch(&self.path) {
+				self.failed.store(true, atomic::Ordering::SeqCst);
+				return Err(error);
+			}
+			*self.write_state() = try!(State::load(&self.path));
+			Ok(())
+		}
 
-	// API helper functions
+		/// Starts a transaction. Changes made through it are staged in memory and only written,
+		/// all together, when it is committed. Blocks until any other open transaction is
+		/// committed or rolled back, so a thread holding a transaction must not write through
+		/// the database directly.
+		pub fn begin(&self) -> DBResult<Transaction<'_>> {
+			try!(self.check_writable());
+			let writer = self.lock_writer();
+			let mut state = self.read_state().clone();
+			state.resources_changed = false;
+			state.components_changed = false;
+			state.instances_changed = false;
+			state.indexes_changed = false;
+
+			Ok(Transaction {
+				database: self,
+				_writer: writer,
+				state: Some(state),
+				operations: Vec::new()
+			})
+		}
 
-	fn select(resource_name: &str, component_name: &str, instance_id: usize) -> DBResult<Data> {
-		Err(DatabaseError::GenericError)
-	}
+		/// Defines a new resource. Returns `AlreadyDefined` if the name is taken.
+		pub fn add_resource(&self, resource_name: &str, resource_type: ResourceIO,	instance_id: Option<usize>) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.add_resource(resource_name, resource_type, instance_id));
+			transaction.commit()
+		}
 
-	fn insert(resource_name: &str, component_name: &str, instance_id: usize, data: Tainted) -> DBResult<()> {
-		Ok(())
-	}
-	fn update(resource_name: &str, component_name: &str, instance_id: usize, data: Tainted) -> DBResult<()> {
-		Ok(())
-	}
+		/// Defines a resource, or updates the definition of an existing one while keeping its id
+		/// and model.
+		pub fn upsert_resource(&self, resource_name: &str, resource_type: ResourceIO,	instance_id: Option<usize>) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.upsert_resource(resource_name, resource_type, instance_id));
+			transaction.commit()
+		}
 
-	fn merge(resource_name: &str, component_name: &str, instance_id: usize, data: Tainted) -> DBResult<()> {
-		Ok(())
-	}
+		/// Defines a new component. Returns `AlreadyDefined` if the name is taken.
+		pub fn add_component(&self, component_name: &str, data_type: DataType) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.add_component(component_name, data_type));
+			transaction.commit()
+		}
 
-	pub fn next_instance_id() -> DBResult<usize> {
-		let mut instances = try!(internals::Instances::load());
-		let instance_id = instances.next_instance_id;
-		instances.next_instance_id = instances.next_instance_id + 1;
-		try!(instances.save());
+		/// Defines a component, or updates the data type of an existing one while keeping its id
+		/// and data. The data type cannot change to one the stored data does not fit.
+		pub fn upsert_component(&self, component_name: &str, data_type: DataType) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.upsert_component(component_name, data_type));
+			transaction.commit()
+		}
 
-		Ok(instance_id)
-	}
+		/// Renames a resource. Its id, model and data are kept. Returns `AlreadyDefined` if the new
+		/// name is taken.
+		pub fn rename_resource(&self, resource_name: &str, new_name: &str) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.rename_resource(resource_name, new_name));
+			transaction.commit()
+		}
 
-	// API functions
+		/// Renames a component. Its id, the models using it and its data are kept. Returns
+		/// `AlreadyDefined` if the new name is taken.
+		pub fn rename_component(&self, component_name: &str, new_name: &str) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.rename_component(component_name, new_name));
+			transaction.commit()
+		}
 
-	pub fn get_component_data_type(component_name: &str) -> DBResult<DataType> {
-		let components = try!(internals::Components::load());
+		/// Adds a component to the model of a resource. A component the resource inherits from
+		/// one of its ancestors can be added again to override its io type.
+		pub fn add_component_to_model(&self, resource_name: &str, component_name: &str, io_type: DataIO) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.add_component_to_model(resource_name, component_name, io_type));
+			transaction.commit()
+		}
 
-		// the component must exist
-		if !components.components.contains_key(component_name) {
-			return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)));
+		/// Declares the parent of a resource, or declares it a root of the hierarchy when `None`.
+		/// A resource without a declared parent is the child of the resource with the longest name
+		/// that is a path prefix of its own, so "/blog/" is the parent of "/blog/{username}/".
+		/// Returns `MalformedStructure` if the resource would become its own ancestor.
+		pub fn set_parent(&self, resource_name: &str, parent_name: Option<&str>) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.set_parent(resource_name, parent_name));
+			transaction.commit()
 		}
 
-		// get component id
-		let component_id = components.components.get(component_name).unwrap();
+		/// Forgets the declared parent of a resource, so its parent is derived from its path again.
+		pub fn clear_parent(&self, resource_name: &str) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.clear_parent(resource_name));
+			transaction.commit()
+		}
 
-		// get type
-		let data_type: &DataType = components.component_data_types.get(&component_id).unwrap();
+		/// Creates a new instance of a FORM resource and returns its id.
+		pub fn create_instance(&self, resource_name: &str) -> DBResult<usize> {
+			let mut transaction = try!(self.begin());
+			let instance_id = try!(transaction.create_instance(resource_name));
+			try!(transaction.commit());
 
-		// ?? done with Copy/Clone? Without Copy/Clone you cannot move this out of scope
-		Ok(*data_type)
-	}
+			Ok(instance_id)
+		}
 
-	pub fn is_static_resource(resource_name: &str) -> DBResult<bool> {
-		let resources = try!(internals::Resources::load());
+		/// Removes an instance from a resource, along with all of its data.
+		pub fn delete_instance(&self, resource_name: &str, instance_id: usize) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.delete_instance(resource_name, instance_id));
+			transaction.commit()
+		}
 
-		// the resource must exist
-		if !resources.resources.contains_key(resource_name) {
-			return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)));
+		/// Removes a component from the model of a resource, along with the data the resource's
+		/// instances, and those of its descendants, can no longer reach. An inherited component is
+		/// only removed through the ancestor that defines it, removing it through the resource
+		/// returns `ComponentNotDefined`.
+		pub fn remove_component_from_model(&self, resource_name: &str, component_name: &str) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.remove_component_from_model(resource_name, component_name));
+			transaction.commit()
 		}
 
-		// get static flag
-		let static_flag = (ResourceIO::STATIC == resources.resources.get(resource_name).unwrap().1);
+		/// Removes a resource, its model and the data of its instances. Its children fall back to
+		/// the parent derived from their paths.
+		pub fn delete_resource(&self, resource_name: &str) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.delete_resource(resource_name));
+			transaction.commit()
+		}
 
-		Ok(static_flag)
-	}
+		/// Removes a component and all of its data. A component that is still part of a resource
+		/// model is only removed when forced, in which case it is removed from those models too.
+		pub fn delete_component(&self, component_name: &str, force: bool) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.delete_component(component_name, force));
+			transaction.commit()
+		}
 
-	pub fn load_static_model(resource_name: &str) -> DBResult<HashMap<String, ComponentInstance>> {
-		let resources = try!(internals::Resources::load());
-		let components = try!(internals::Components::load());
-		let instances = try!(internals::Instances::load());
+		/// Declares a secondary index on the values of a component and builds it from the data
+		/// already stored. A component has at most one index.
+		pub fn add_index(&self, component_name: &str, index_type: IndexType) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.add_index(component_name, index_type));
+			transaction.commit()
+		}
 
-		// the resource must exist
-		if !resources.resources.contains_key(resource_name) {
-			return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)));
+		pub fn remove_index(&self, component_name: &str) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.remove_index(component_name));
+			transaction.commit()
 		}
 
-		// the resource must be static
-		if !try!(is_static_resource(resource_name)) {
-			return Err(DatabaseError::MalformedStructure(format!("Resource is not static: {}", resource_name)));
+		// API helper functions
+
+		/// Returns the data stored for a single component of a resource instance, or null if the
+		/// instance has no data for it.
+		pub fn select(&self, resource_name: &str, component_name: &str, instance_id: usize) -> DBResult<Data> {
+			self.read_state().select(resource_name, component_name, instance_id)
 		}
 
-		// get resource id
-		let resource_id = resources.resources.get(resource_name).unwrap().0;
+		/// Writes data for a component of a resource instance that has no data yet. The data is
+		/// parsed into the component data type, see `Tainted::parse`.
+		pub fn insert(&self, resource_name: &str, component_name: &str, instance_id: usize, data: Tainted) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.insert(resource_name, component_name, instance_id, data));
+			transaction.commit()
+		}
 
-		// get model
-		let mut model: HashMap<String, ComponentInstance> = HashMap::new();
-		let model_component = resources.models.get(&resource_id).unwrap();
-		for (component_id, io_type) in model_component {
-			println!("Getting component");
-			// get component data
-			let component_name = components.component_names.get(&component_id).unwrap();
-			let component_data_type = components.component_data_types.get(&component_id).unwrap();
+		/// Overwrites the existing data for a component of a resource instance. Blank input for a
+		/// component that is not a string removes the data.
+		pub fn update(&self, resource_name: &str, component_name: &str, instance_id: usize, data: Tainted) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.update(resource_name, component_name, instance_id, data));
+			transaction.commit()
+		}
 
-			// the component must be static
-			if *io_type != DataIO::STATIC {
-				return Err(DatabaseError::MalformedStructure(format!("Component is not static: {}", resource_name)));
-			}
+		/// Writes data for a component of a resource instance, whether or not it already has data.
+		pub fn merge(&self, resource_name: &str, component_name: &str, instance_id: usize, data: Tainted) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.merge(resource_name, component_name, instance_id, data));
+			transaction.commit()
+		}
 
-			let instance = ComponentInstance {
-				component_id: *component_id,
-				component_name: component_name.to_string(),
-				component_data_type: *component_data_type,
-				component_io_type: *io_type,
-				data: Data::STRING("".to_string())
-			};
+		/// Writes typed data for a component of a resource instance, whether or not it already has
+		/// data. Unlike `merge`, the data does not come from a user, so it is not filtered.
+		pub fn merge_data(&self, resource_name: &str, component_name: &str, instance_id: usize, data: Data) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.merge_data(resource_name, component_name, instance_id, data));
+			transaction.commit()
+		}
 
-			model.insert(component_name.to_string(), instance);
+		/// Adds an element to the list or set component of a resource instance. The element is
+		/// parsed into the element type of the component. A set ignores elements it already holds.
+		pub fn append_element(&self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.append_element(resource_name, component_name, instance_id, element));
+			transaction.commit()
 		}
 
-		Ok(model)
-	}
+		/// Removes every occurrence of an element from the list or set component of a resource
+		/// instance.
+		pub fn remove_element(&self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<()> {
+			let mut transaction = try!(self.begin());
+			try!(transaction.remove_element(resource_name, component_name, instance_id, element));
+			transaction.commit()
+		}
 
-	pub fn load_model(resource_name: &str, instance_id: usize) -> DBResult<HashMap<String, ComponentInstance>> {
-		let resources = try!(internals::Resources::load());
-		let components = try!(internals::Components::load());
-		let instances = try!(internals::Instances::load());
+		/// Returns whether the list or set component of a resource instance holds an element. The
+		/// element is parsed into the element type of the component.
+		pub fn contains_element(&self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<bool> {
+			self.read_state().contains_element(resource_name, component_name, instance_id, element)
+		}
+
+		pub fn next_instance_id(&self) -> DBResult<usize> {
+			let mut transaction = try!(self.begin());
+			let instance_id = try!(transaction.next_instance_id());
+			try!(transaction.commit());
 
-		// the resource must exist
-		if !resources.resources.contains_key(resource_name) {
-			return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)));
+			Ok(instance_id)
 		}
 
-		// get resource id
-		let resource_id = resources.resources.get(resource_name).unwrap().0;
+		// Blob functions
 
-		// get model
-		let mut model: HashMap<String, ComponentInstance> = HashMap::new();
-		let model_components = resources.models.get(&resource_id).unwrap();
-		for (component_id, io_type) in model_components {
-			// get component data
-			let component_name = components.component_names.get(&component_id).unwrap();
-			let component_data_type = components.component_data_types.get(&component_id).unwrap();
+		/// Streams data into a blob file and returns the blob, to be saved in a BLOB component.
+		/// The data is never held in memory as a whole.
+		pub fn write_blob<R: Read>(&self, reader: &mut R) -> DBResult<Blob> {
+			try!(self.check_writable());
+			let (hash, length) = try!(internals::write_blob(&self.path, reader));
 
-			// get data
-			let data: Data = match instances.instances.get(&component_id).unwrap().get(&instance_id) {
-				Some(d) => d.copy(),
-				None => Data::STRING("".to_string())
-			};
+			Ok(Blob {
+				hash: hash,
+				length: length
+			})
+		}
 
-			let instance = ComponentInstance {
-				component_id: *component_id,
-				component_name: component_name.to_string(),
-				component_data_type: *component_data_type,
-				component_io_type: *io_type,
-				data: data
-			};
-			
-			model.insert(component_name.to_string(), instance);
+		/// Opens the data of a blob for streaming.
+		pub fn read_blob(&self, blob: &Blob) -> DBResult<File> {
+			internals::open_blob(&self.path, &blob.hash)
 		}
 
-		Ok(model)
-	}
+		/// Removes the blob files no component data refers to and returns how many were removed.
+		/// Blobs are kept when the data referring to them is removed, so they are only cleaned up
+		/// here. A blob that was written but not saved yet is removed as well, and saving it
+		/// afterwards fails with `FileNotFound`, so it has to be written again.
+		pub fn remove_unused_blobs(&self) -> DBResult<usize> {
+			try!(self.check_writable());
+			let _writer = self.lock_writer();
+			let used = self.read_state().blob_hashes();
+			internals::remove_unused_blobs(&self.path, &used)
+		}
 
-	pub fn save_model(model: HashMap<String, ComponentInstance>, resource_name: &str, instance_id: usize) -> DBResult<()> {
-		let resources = try!(internals::Resources::load());
-		let components = try!(internals::Components::load());
-		let mut instances = try!(internals::Instances::load());
+		// API functions
 
-		// get resource id
-		let resource_id = resources.resources.get(resource_name).unwrap().0;
+		pub fn get_component_data_type(&self, component_name: &str) -> DBResult<DataType> {
+			self.read_state().get_component_data_type(component_name)
+		}
 
-		for (component_name, instance) in model {
-			// get instance and update instances with data
-			let component_id = instance.component_id;
+		pub fn is_static_resource(&self, resource_name: &str) -> DBResult<bool> {
+			self.read_state().is_static_resource(resource_name)
+		}
 
-			// check the component data type
-			let component_data_type = components.component_data_types.get(&component_id).unwrap();
-			// TODO: filter here? Tainted?
+		pub fn load_static_model(&self, resource_name: &str) -> DBResult<HashMap<String, ComponentInstance>> {
+			self.read_state().load_static_model(resource_name)
+		}
 
-			// check the component io type
-			let component_io_type = resources.models.get(&resource_id).unwrap().get(&component_id).unwrap(); // io type is per resource and comes from the model
-			if *component_io_type == DataIO::DB_READ_ONLY || *component_io_type == DataIO::STATIC {
-				// do not try to save any read only or static components
-				continue;
-			}
+		pub fn load_model(&self, resource_name: &str, instance_id: usize) -> DBResult<HashMap<String, ComponentInstance>> {
+			self.read_state().load_model(resource_name, instance_id)
+		}
 
-			instances.instances.get_mut(&component_id).unwrap().insert(instance_id, instance.data);
+		/// Finds the resource that handles a request path. A resource with the exact name wins
+		/// over route patterns. The instance of a route pattern is only looked up, never created.
+		pub fn match_route(&self, path: &str) -> DBResult<Route> {
+			self.read_state().match_route(path)
 		}
 
-		Ok(())
-	}
+		/// Finds the resource that handles a request path, creating the instance for the captured
+		/// parameters of a route pattern the first time the path is seen.
+		pub fn resolve_route(&self, path: &str) -> DBResult<Route> {
+			// most paths are seen before, which only needs a read
+			let route = try!(self.match_route(path));
+			if route.instance_id.is_some() || route.params.is_empty() {
+				return Ok(route);
+			}
 
-	/// Defines the internal workings of the database. This includes filesystems layout,
-	/// file I/O, and database design.
-	mod internals {
-		use db;
-		use std::path::Path;
-		use std::collections::{HashMap, HashSet};
-		use std::fs;
-		use std::fs::{File, OpenOptions};
-		use std::io::{BufReader, ErrorKind};
-		use bincode::{decode, encode, decode_from, encode_into, SizeLimit};
-		use rustc_serialize::{Encodable, Decodable};
-		use regex::Regex;
+			let mut transaction = try!(self.begin());
+			let route = try!(transaction.resolve_route(path));
+			try!(transaction.commit());
 
-		static DATA_FOLDER: &'static str = "data/";
-		static RESOURCES_FILE: &'static str = "data/resources.db";
-		static COMPONENTS_FILE: &'static str = "data/components.db";
-		static INSTANCES_FILE: &'static str = "data/instances.db";
+			Ok(route)
+		}
 
-		// structs to serialize to file
+		/// Saves the data of a model for a resource instance. Every component in the model is
+		/// validated before anything is written, so either all writable components are saved or
+		/// none are. Returns the names of the components that were skipped because they are
+		/// read only or static.
+		pub fn save_model(&self, model: HashMap<String, ComponentInstance>, resource_name: &str, instance_id: usize) -> DBResult<Vec<String>> {
+			let mut transaction = try!(self.begin());
+			let skipped = try!(transaction.save_model(model, resource_name, instance_id));
+			try!(transaction.commit());
 
-		#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug)]
-		pub struct Resources {
-			pub resources: HashMap<String, (usize, db::ResourceIO)>, // resource name : (resource id, static flag)
-			pub resource_instances: HashMap<usize, usize>, // resource id : instance id
-			pub models: HashMap<usize, HashMap<usize, db::DataIO>>, // resource id : [component id : data io]
-			pub next_resource_id: usize, // keeps track of resource ids
+			Ok(skipped)
 		}
 
-		impl Resources {
-			fn new() -> Resources {
-				Resources {
-					resources: HashMap::new(),
-					resource_instances: HashMap::new(),
-					models: HashMap::new(),
-					next_resource_id: 1,
-				}
+		fn check_writable(&self) -> DBResult<()> {
+			if self.read_only {
+				return Err(DatabaseError::ReadOnlyDatabase(format!("Database was opened read only: {}", self.path.display())));
 			}
 
-			pub fn load() -> db::DBResult<Resources> {
-				match load_from_file::<Resources>(RESOURCES_FILE) {
-					Ok(r) => Ok(r),
-					Err(error) => Err(error)
-				}
+			// the files may hold part of the failed commit, which only replaying the log on open completes
+			if self.failed.load(atomic::Ordering::SeqCst) {
+				return Err(DatabaseError::DatabaseFailed(format!("Database must be reopened after a failed commit: {}", self.path.display())));
 			}
 
-			pub fn save(&self) -> db::DBResult<()> {
-				save_to_file::<Resources>(RESOURCES_FILE, &self)
-			}
+			Ok(())
 		}
 
-		#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug)]
-		pub struct Components {
-			pub components: HashMap<String, usize>,
-			pub component_names: HashMap<usize, String>,
-			pub component_data_types: HashMap<usize, db::DataType>,
-			pub next_component_id: usize
-		}
+		// Introspection functions
 
-		impl Components {
-			fn new() -> Components {
-				Components {
-					components: HashMap::new(),
-					component_names: HashMap::new(),
-					component_data_types: HashMap::new(),
-					next_component_id: 0
-				}
-			}
+		/// Lists every resource with its io type and instance ids, sorted by name.
+		pub fn list_resources(&self) -> DBResult<Vec<ResourceInfo>> {
+			self.read_state().list_resources()
+		}
 
-			pub fn load() -> db::DBResult<Components> {
-				match load_from_file::<Components>(COMPONENTS_FILE) {
-					Ok(r) => Ok(r),
-					Err(error) => Err(error)
-				}
-			}
+		/// Lists every component with its data type, sorted by name.
+		pub fn list_components(&self) -> DBResult<Vec<ComponentInfo>> {
+			self.read_state().list_components()
+		}
 
-			pub fn save(&self) -> db::DBResult<()> {
-				save_to_file::<Components>(COMPONENTS_FILE, &self)
-			}
+		/// Returns the model of a resource as component name : io type, including the components
+		/// it inherits.
+		pub fn describe_model(&self, resource_name: &str) -> DBResult<HashMap<String, DataIO>> {
+			self.read_state().describe_model(resource_name)
 		}
 
-		#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug)]
-		pub struct Instances {
-			pub instances: HashMap<usize, HashMap<usize, db::Data>>, // component id : [instance id : Data]
-			pub next_instance_id: usize
+		/// Returns the parent of a resource, if it has one.
+		pub fn parent(&self, resource_name: &str) -> DBResult<Option<String>> {
+			self.read_state().parent(resource_name)
 		}
 
-		impl Instances {
-			fn new() -> Instances {
-				Instances {
-					instances: HashMap::new(),
-					next_instance_id: 0
-				}
-			}
+		/// Lists the children of a resource, sorted by name.
+		pub fn children(&self, resource_name: &str) -> DBResult<Vec<String>> {
+			self.read_state().children(resource_name)
+		}
 
-			pub fn load() -> db::DBResult<Instances> {
-				match load_from_file::<Instances>(INSTANCES_FILE) {
-					Ok(r) => Ok(r),
-					Err(error) => Err(error)
-				}
-			}
+		/// Lists the ancestors of a resource, nearest first.
+		pub fn ancestors(&self, resource_name: &str) -> DBResult<Vec<String>> {
+			self.read_state().ancestors(resource_name)
+		}
 
-			pub fn save(&self) -> db::DBResult<()> {
-				save_to_file::<Instances>(INSTANCES_FILE, &self)
-			}
+		/// Walks the tree below a resource depth first, listing every descendant before its own
+		/// children and siblings in name order.
+		pub fn descendants(&self, resource_name: &str) -> DBResult<Vec<String>> {
+			self.read_state().descendants(resource_name)
 		}
 
-		pub fn start_from_scratch() -> db::DBResult<()> {
-			// create all directories and files
-			try!(create_directory(DATA_FOLDER));
-			try!(create_file(RESOURCES_FILE));
-			try!(create_file(COMPONENTS_FILE));
-			try!(create_file(INSTANCES_FILE));
+		/// Lists the ids of every instance of a resource, sorted.
+		pub fn list_instances(&self, resource_name: &str) -> DBResult<Vec<usize>> {
+			self.read_state().list_instances(resource_name)
+		}
 
-			// create default data
-			let resources = Resources::new();
-			try!(resources.save());
+		/// Returns whether an instance belongs to a resource.
+		pub fn has_instance(&self, resource_name: &str, instance_id: usize) -> DBResult<bool> {
+			self.read_state().has_instance(resource_name, instance_id)
+		}
 
-			let components = Components::new();
-			try!(components.save());
+		/// Lists the ids of every instance that has data for a component in the model of a
+		/// resource, sorted.
+		pub fn instances_with_data(&self, resource_name: &str) -> DBResult<Vec<usize>> {
+			self.read_state().instances_with_data(resource_name)
+		}
 
-			let instances = Instances::new();
-			try!(instances.save());
+		// Query functions
 
-			Ok(())
+		/// Lists the resources whose model has a component, including through inheritance,
+		/// sorted by name.
+		pub fn resources_with_component(&self, component_name: &str) -> DBResult<Vec<String>> {
+			self.read_state().resources_with_component(component_name)
 		}
 
-		fn create_directory(filename: &'static str) -> db::DBResult<()> {
-			let path = Path::new(&filename);
-			match fs::create_dir(&filename) {
-				Ok(dir) => Ok(()),
-				Err(error) => {
-					match error.kind() {
-						ErrorKind::AlreadyExists => Ok(()),
-						_ => Err(db::DatabaseError::CouldNotCreateDirectory(format!("Could not create directory, {}: {}", filename, error)))
-					}
-				}
-			}
+		/// Lists every component that the models of more than one resource have, sorted by name.
+		pub fn shared_components(&self) -> DBResult<Vec<SharedComponent>> {
+			self.read_state().shared_components()
 		}
 
-		fn create_file(filename: &'static str) -> db::DBResult<File> {
-			let path = Path::new(&filename);
-			match File::create(path) {
-				Ok(file) => Ok(file),
-				Err(error)  => Err(db::DatabaseError::CouldNotCreateFile(format!("Could not create file, {}: {}", filename, error)))
-			}
+		/// Finds the instances of every resource that hold a value for a component, such as all
+		/// instances where "username" is "alice". A list or set component matches when it holds
+		/// the value as one of its elements. Integers match whatever their width. Sorted by
+		/// resource name and instance id.
+		pub fn instances_with_value(&self, component_name: &str, value: &Data) -> DBResult<Vec<ResourceInstance>> {
+			self.read_state().instances_with_value(component_name, value)
 		}
 
-		fn open_file_for_reading(filename: &'static str) -> db::DBResult<File> {
-			let path = Path::new(&filename);
-			match OpenOptions::new().read(true).write(false).open(path) {
-				Ok(file) => Ok(file),
-				Err(error)  => return Err(db::DatabaseError::FileNotFound(format!("Could not open file, {}: {}", filename, error)))
-			}
+		/// Follows references from an instance of a resource, up to a number of references deep
+		/// or as far as they go. Every instance is reached once, by the fewest references, and
+		/// instances at the same depth are sorted by resource name and instance id.
+		pub fn traverse_references(&self, resource_name: &str, instance_id: usize, direction: Direction, max_depth: Option<usize>) -> DBResult<Vec<ReachedInstance>> {
+			self.read_state().traverse_references(resource_name, instance_id, direction, max_depth)
 		}
 
-		fn open_file_for_writing(filename: &'static str) -> db::DBResult<File> {
-			let path = Path::new(&filename);
-			match OpenOptions::new().read(true).write(true).open(path) {
-				Ok(file) => Ok(file),
-				Err(error)  => return Err(db::DatabaseError::FileNotFound(format!("Could not open file, {}: {}", filename, error)))
-			}
+		/// Finds the instances of every resource that hold a value for a component from `from` up
+		/// to and including `to`, in the order of their values. Either bound may be left open.
+		/// The component must have an ordered index.
+		pub fn instances_in_range(&self, component_name: &str, from: Option<&Data>, to: Option<&Data>) -> DBResult<Vec<ResourceInstance>> {
+			self.read_state().instances_in_range(component_name, from, to)
 		}
 
-		fn load_from_file<T>(filename: &'static str) -> db::DBResult<T> where T: Decodable {
-			let file = try!(open_file_for_reading(&filename));
+		// The shared state is only ever replaced as a whole, so a thread that panicked while
+		// holding one of these locks cannot have left it half updated. Poisoning is ignored.
 
-			// TODO: check if empty, if so then do something useful
-			let mut reader = BufReader::new(&file);
-
-			match decode_from(&mut reader, SizeLimit::Infinite) {
-				Ok(value) => Ok(value),
-				Err(error) => {
-					return Err(db::DatabaseError::FileNotFound(format!("Could not decode file, {}: {}", filename, error)));
-				}
-			}
+		fn lock_writer(&self) -> MutexGuard<'_, ()> {
+			self.writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
 		}
 
-		fn save_to_file<T>(filename: &'static str, value: &T) -> db::DBResult<()> where T: Encodable {	
-			let mut file = try!(open_file_for_writing(&filename));
-
-			// save definitions back to file
-			match encode_into(&value, &mut file, SizeLimit::Infinite) {
-				Err(error) => { return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to save to file: {}, Reason: {}", filename, error))); }
-				_ => ()
-			};
-
-			Ok(())
+		fn read_state(&self) -> RwLockReadGuard<'_, State> {
+			self.state.read().unwrap_or_else(|poisoned| poisoned.into_inner())
 		}
 
-		/*
-		// TODO: return an error instead of panicing
-		fn encode<T>(value: &T) -> Vec<u8> where T: Encodable {
-			encode(&value, SizeLimit::Infinite).unwrap_or(panic!("Could not encode Value<T>."))
+		fn write_state(&self) -> RwLockWriteGuard<'_, State> {
+			self.state.write().unwrap_or_else(|poisoned| poisoned.into_inner())
 		}
+	}
 
-		// TODO: return an error instead of panicing
-		fn decode<T>(encoded: &Vec<u8>) -> T where T: Decodable {
-			bincode::decode(&encoded[..]).unwrap_or(panic!("Could not decode to Value<T>."))
-		}
-		*/
+	/// A group of operations that is applied to the database as one unit. Operations are staged
+	/// in memory and written together by `commit`, which goes through the write-ahead log so a
+	/// crash can never leave part of a transaction on disk. The first operation that fails rolls
+	/// the whole transaction back, after which every call returns `TransactionRolledBack`.
+	/// Dropping a transaction without committing it also rolls it back.
+	pub struct Transaction<'a> {
+		database: &'a Database,
+		_writer: MutexGuard<'a, ()>, // keeps other transactions out until this one ends
+		state: Option<State>, // None once rolled back
+		operations: Vec<String>
 	}
-}
 
-/*
-#[test]
-fn test_setup() {
-	match db::reset() {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
-}
-*/
+	impl<'a> Transaction<'a> {
+		/// Writes every staged change to the database files, then makes it visible to readers.
+		pub fn commit(self) -> DBResult<()> {
+			let state = match self.state {
+				Some(state) => state,
+				None => return Err(DatabaseError::TransactionRolledBack("Cannot commit a transaction that was rolled back".to_string()))
+			};
+
+			{ // scope the entry, which borrows the staged state
+				let mut entry = internals::LogEntry::new(self.operations.join("; "));
+				if state.resources_changed {
+					entry.resources = Some(Cow::Borrowed(&state.resources));
+				}
+				if state.components_changed {
+					entry.components = Some(Cow::Borrowed(&state.components));
+				}
+				if state.instances_changed {
+					entry.instances = Some(Cow::Borrowed(&state.instances));
+				}
+				if state.indexes_changed {
+					entry.indexes = Some(Cow::Borrowed(&state.indexes));
+				}
+				if entry.resources.is_none() && entry.components.is_none() && entry.instances.is_none() && entry.indexes.is_none() {
+					// nothing to write
+					return Ok(());
+				}
 
+				// the entry stays in the log if any file could not be written, and clearing the log
+				// with a later commit would lose it, so no more writes are taken
+				if let Err(error) = entry.commit(&self.database.path) {
+					self.database.failed.store(true, atomic::Ordering::SeqCst);
+					return Err(error);
+				}
+			}
 
-/// A static resource means that there is only one instance (copy) of that resource. This means
-/// that it can only hold static components.
+			// the files are written, so the staged state is now the database state
+			*self.database.write_state() = state;
 
-/// A static component means that there is only one instance of it.
-/// Types of IO: DBInput, DBReadOnly, DBBoth, and Static
-/// DB types reference a specific data slot in the database where information is stored. A static 
-/// type is a field that is only there for data processing, such as username and password on
-/// a login page. DB types are only allowed on non-static resources.
+			Ok(())
+		}
+
+		/// Discards every staged change.
+		pub fn rollback(self) {
+			// the staged state is dropped with the transaction
+		}
 
+		/// Applies an operation to the staged state, rolling back the transaction if it fails.
+		fn stage<T, F>(&mut self, operation: String, stage: F) -> DBResult<T> where F: FnOnce(&mut State) -> DBResult<T> {
+			let result = match self.state {
+				Some(ref mut state) => stage(state),
+				None => return Err(DatabaseError::TransactionRolledBack(format!("Transaction was rolled back before: {}", operation)))
+			};
 
-fn get_model(resource_name: &str) -> HashMap<String, db::ComponentInstance> {
-	let model: HashMap<String, db::ComponentInstance> = {
-		let is_static = match db::is_static_resource("/login/") {
-			Err(error) => panic!("{:?}", error),
-			Ok(b) => b
-		};
-		if is_static {
-			// static resource, so just get the associated model
-			match db::load_static_model("/login/") {
-				Err(error) => panic!("{:?}", error),
-				Ok(model) => model
+			match result {
+				Ok(value) => {
+					self.operations.push(operation);
+					Ok(value)
+				},
+				Err(error) => {
+					self.state = None;
+					Err(error)
+				}
 			}
-		} else {
-			// use instance id, if applicable
-			match db::load_model("/login/", 1) {
-				Err(error) => panic!("{:?}", error),
-				Ok(model) => model
+		}
+
+		fn state(&self) -> DBResult<&State> {
+			match self.state {
+				Some(ref state) => Ok(state),
+				None => Err(DatabaseError::TransactionRolledBack("Transaction was rolled back".to_string()))
 			}
 		}
-	};
 
-	model
-}
+		// Admin functions
 
-#[test]
-fn test_database() {
-	// test admin functions
+		pub fn add_resource(&mut self, resource_name: &str, resource_type: ResourceIO,	instance_id: Option<usize>) -> DBResult<()> {
+			self.stage(format!("add_resource {}", resource_name), |state| state.add_resource(resource_name, resource_type, instance_id))
+		}
 
-	match db::reset() {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn upsert_resource(&mut self, resource_name: &str, resource_type: ResourceIO,	instance_id: Option<usize>) -> DBResult<()> {
+			self.stage(format!("upsert_resource {}", resource_name), |state| state.upsert_resource(resource_name, resource_type, instance_id))
+		}
 
-	match db::add_resource("/login/", db::ResourceIO::STATIC, None) {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn add_component(&mut self, component_name: &str, data_type: DataType) -> DBResult<()> {
+			self.stage(format!("add_component {}", component_name), |state| state.add_component(component_name, data_type))
+		}
 
-	let blog_post_instance_id = match db::next_instance_id() {
-		Err(error) => panic!("{:?}", error),
-		Ok(id) => id
-	};
+		pub fn upsert_component(&mut self, component_name: &str, data_type: DataType) -> DBResult<()> {
+			self.stage(format!("upsert_component {}", component_name), |state| state.upsert_component(component_name, data_type))
+		}
 
-	match db::add_resource("/blog/username/my_first_post/", db::ResourceIO::FORM, Some(blog_post_instance_id)) {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn rename_resource(&mut self, resource_name: &str, new_name: &str) -> DBResult<()> {
+			self.stage(format!("rename_resource {} {}", resource_name, new_name), |state| state.rename_resource(resource_name, new_name))
+		}
 
-	match db::add_component("blogpost", db::DataType::STRING) {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn rename_component(&mut self, component_name: &str, new_name: &str) -> DBResult<()> {
+			self.stage(format!("rename_component {} {}", component_name, new_name), |state| state.rename_component(component_name, new_name))
+		}
 
-	match db::add_component("username", db::DataType::STRING) {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn add_component_to_model(&mut self, resource_name: &str, component_name: &str, io_type: DataIO) -> DBResult<()> {
+			self.stage(format!("add_component_to_model {} {}", resource_name, component_name), |state| state.add_component_to_model(resource_name, component_name, io_type))
+		}
 
-	match db::add_component("password", db::DataType::PASSWORD) {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn set_parent(&mut self, resource_name: &str, parent_name: Option<&str>) -> DBResult<()> {
+			self.stage(format!("set_parent {} {:?}", resource_name, parent_name), |state| state.set_parent(resource_name, parent_name))
+		}
 
-	match db::add_component_to_model("/login/", "username", db::DataIO::STATIC) {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn clear_parent(&mut self, resource_name: &str) -> DBResult<()> {
+			self.stage(format!("clear_parent {}", resource_name), |state| state.clear_parent(resource_name))
+		}
 
-	match db::add_component_to_model("/login/", "password", db::DataIO::STATIC) {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn create_instance(&mut self, resource_name: &str) -> DBResult<usize> {
+			self.stage(format!("create_instance {}", resource_name), |state| state.create_instance(resource_name))
+		}
 
-	match db::add_component_to_model("/blog/username/my_first_post/", "blogpost", db::DataIO::DB_READ_ONLY) {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn delete_instance(&mut self, resource_name: &str, instance_id: usize) -> DBResult<()> {
+			self.stage(format!("delete_instance {} {}", resource_name, instance_id), |state| state.delete_instance(resource_name, instance_id))
+		}
 
-	// test API functions
+		pub fn remove_component_from_model(&mut self, resource_name: &str, component_name: &str) -> DBResult<()> {
+			self.stage(format!("remove_component_from_model {} {}", resource_name, component_name), |state| state.remove_component_from_model(resource_name, component_name))
+		}
 
-	let data_type1 = match db::get_component_data_type("username") {
-		Err(error) => panic!("{:?}", error),
-		Ok(data_type) => data_type
-	};
-	assert_eq!(db::DataType::STRING, data_type1);
+		pub fn delete_resource(&mut self, resource_name: &str) -> DBResult<()> {
+			self.stage(format!("delete_resource {}", resource_name), |state| state.delete_resource(resource_name))
+		}
 
-	let data_type2 = match db::get_component_data_type("password") {
-		Err(error) => panic!("{:?}", error),
-		Ok(data_type) => data_type
-	};
-	assert_eq!(db::DataType::PASSWORD, data_type2);
+		pub fn delete_component(&mut self, component_name: &str, force: bool) -> DBResult<()> {
+			self.stage(format!("delete_component {}", component_name), |state| state.delete_component(component_name, force))
+		}
 
-	let model1 = get_model("/login/");
+		pub fn add_index(&mut self, component_name: &str, index_type: IndexType) -> DBResult<()> {
+			self.stage(format!("add_index {}", component_name), |state| state.add_index(component_name, index_type))
+		}
 
-	match db::save_model(model1, "/login/", 1) {
-		Err(error) => panic!("{:?}", error),
-		_ => ()
-	};
+		pub fn remove_index(&mut self, component_name: &str) -> DBResult<()> {
+			self.stage(format!("remove_index {}", component_name), |state| state.remove_index(component_name))
+		}
 
-	let model2 = get_model("/login/");
+		// API helper functions
 
-	assert!(model2.contains_key("username"));
+		pub fn select(&self, resource_name: &str, component_name: &str, instance_id: usize) -> DBResult<Data> {
+			try!(self.state()).select(resource_name, component_name, instance_id)
+		}
 
+		pub fn insert(&mut self, resource_name: &str, component_name: &str, instance_id: usize, data: Tainted) -> DBResult<()> {
+			self.stage(format!("insert {} {} {}", resource_name, component_name, instance_id), |state| state.write_component(resource_name, component_name, instance_id, data, WriteMode::INSERT))
+		}
 
-	// test static resource
-	{
-		let is_static = match db::is_static_resource("/login/") {
-			Err(error) => panic!("{:?}", error),
-			Ok(b) => b
-		};
-		assert!(is_static);
+		pub fn update(&mut self, resource_name: &str, component_name: &str, instance_id: usize, data: Tainted) -> DBResult<()> {
+			self.stage(format!("update {} {} {}", resource_name, component_name, instance_id), |state| state.write_component(resource_name, component_name, instance_id, data, WriteMode::UPDATE))
+		}
 
-		// static resource, so just get the associated model
-		match db::load_static_model("/login/") {
-			Err(error) => panic!("{:?}", error),
-			Ok(model) => model
-		};
-	}
+		pub fn merge(&mut self, resource_name: &str, component_name: &str, instance_id: usize, data: Tainted) -> DBResult<()> {
+			self.stage(format!("merge {} {} {}", resource_name, component_name, instance_id), |state| state.write_component(resource_name, component_name, instance_id, data, WriteMode::MERGE))
+		}
 
-	// test form resource
-	{
-		let is_static = match db::is_static_resource("/blog/username/my_first_post/") {
-			Err(error) => panic!("{:?}", error),
-			Ok(b) => b
-		};
-		assert!(!is_static);
+		pub fn merge_data(&mut self, resource_name: &str, component_name: &str, instance_id: usize, data: Data) -> DBResult<()> {
+			self.stage(format!("merge_data {} {} {}", resource_name, component_name, instance_id), |state| state.write_data(resource_name, component_name, instance_id, data, WriteMode::MERGE))
+		}
 
-		// get the associated model
-		match db::load_model("/blog/username/my_first_post/", blog_post_instance_id) {
-			Err(error) => panic!("{:?}", error),
-			Ok(model) => model
-		};
-	}
+		pub fn append_element(&mut self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<()> {
+			self.stage(format!("append_element {} {} {}", resource_name, component_name, instance_id), |state| state.append_element(resource_name, component_name, instance_id, element))
+		}
 
+		pub fn remove_element(&mut self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<()> {
+			self.stage(format!("remove_element {} {} {}", resource_name, component_name, instance_id), |state| state.remove_element(resource_name, component_name, instance_id, element))
+		}
 
-	// test input data
+		pub fn contains_element(&self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<bool> {
+			try!(self.state()).contains_element(resource_name, component_name, instance_id, element)
+		}
 
+		pub fn next_instance_id(&mut self) -> DBResult<usize> {
+			self.stage("next_instance_id".to_string(), |state| state.next_instance_id())
+		}
 
-	// test read only data
+		// API functions
 
+		pub fn get_component_data_type(&self, component_name: &str) -> DBResult<DataType> {
+			try!(self.state()).get_component_data_type(component_name)
+		}
 
-	// test input and read only data
+		pub fn is_static_resource(&self, resource_name: &str) -> DBResult<bool> {
+			try!(self.state()).is_static_resource(resource_name)
+		}
 
+		pub fn load_static_model(&self, resource_name: &str) -> DBResult<HashMap<String, ComponentInstance>> {
+			try!(self.state()).load_static_model(resource_name)
+		}
 
-	// test static data	
-}
+		pub fn load_model(&self, resource_name: &str, instance_id: usize) -> DBResult<HashMap<String, ComponentInstance>> {
+			try!(self.state()).load_model(resource_name, instance_id)
+		}
 
+		pub fn match_route(&self, path: &str) -> DBResult<Route> {
+			try!(self.state()).match_route(path)
+		}
 
+		pub fn resolve_route(&mut self, path: &str) -> DBResult<Route> {
+			self.stage(format!("resolve_route {}", path), |state| state.resolve_route(path))
+		}
 
+		pub fn save_model(&mut self, model: HashMap<String, ComponentInstance>, resource_name: &str, instance_id: usize) -> DBResult<Vec<String>> {
+			self.stage(format!("save_model {} {}", resource_name, instance_id), |state| state.save_model(model, resource_name, instance_id))
+		}
 
+		// Introspection functions
 
+		pub fn list_resources(&self) -> DBResult<Vec<ResourceInfo>> {
+			try!(self.state()).list_resources()
+		}
 
+		pub fn list_components(&self) -> DBResult<Vec<ComponentInfo>> {
+			try!(self.state()).list_components()
+		}
 
+		pub fn describe_model(&self, resource_name: &str) -> DBResult<HashMap<String, DataIO>> {
+			try!(self.state()).describe_model(resource_name)
+		}
 
+		pub fn parent(&self, resource_name: &str) -> DBResult<Option<String>> {
+			try!(self.state()).parent(resource_name)
+		}
 
+		pub fn children(&self, resource_name: &str) -> DBResult<Vec<String>> {
+			try!(self.state()).children(resource_name)
+		}
 
+		pub fn ancestors(&self, resource_name: &str) -> DBResult<Vec<String>> {
+			try!(self.state()).ancestors(resource_name)
+		}
 
+		pub fn descendants(&self, resource_name: &str) -> DBResult<Vec<String>> {
+			try!(self.state()).descendants(resource_name)
+		}
 
+		pub fn list_instances(&self, resource_name: &str) -> DBResult<Vec<usize>> {
+			try!(self.state()).list_instances(resource_name)
+		}
 
+		pub fn has_instance(&self, resource_name: &str, instance_id: usize) -> DBResult<bool> {
+			try!(self.state()).has_instance(resource_name, instance_id)
+		}
 
+		pub fn instances_with_data(&self, resource_name: &str) -> DBResult<Vec<usize>> {
+			try!(self.state()).instances_with_data(resource_name)
+		}
 
+		// Query functions
 
+		pub fn resources_with_component(&self, component_name: &str) -> DBResult<Vec<String>> {
+			try!(self.state()).resources_with_component(component_name)
+		}
 
+		pub fn shared_components(&self) -> DBResult<Vec<SharedComponent>> {
+			try!(self.state()).shared_components()
+		}
 
+		pub fn instances_with_value(&self, component_name: &str, value: &Data) -> DBResult<Vec<ResourceInstance>> {
+			try!(self.state()).instances_with_value(component_name, value)
+		}
 
+		pub fn traverse_references(&self, resource_name: &str, instance_id: usize, direction: Direction, max_depth: Option<usize>) -> DBResult<Vec<ReachedInstance>> {
+			try!(self.state()).traverse_references(resource_name, instance_id, direction, max_depth)
+		}
 
+		pub fn instances_in_range(&self, component_name: &str, from: Option<&Data>, to: Option<&Data>) -> DBResult<Vec<ResourceInstance>> {
+			try!(self.state()).instances_in_range(component_name, from, to)
+		}
+	}
 
+	/// The decoded database files, along with which of them have been changed. The files are
+	/// shared with the state a transaction was started from until they are changed, so staging a
+	/// change only copies the files it touches.
+	#[derive(Clone)]
+	struct State {
+		directory: PathBuf, // where blob files are looked up
+		resources: Arc<internals::Resources>,
+		components: Arc<internals::Components>,
+		instances: Arc<internals::Instances>,
+		indexes: Arc<internals::Indexes>,
+		resources_changed: bool,
+		components_changed: bool,
+		instances_changed: bool,
+		indexes_changed: bool
+	}
 
+	impl State {
+		fn load(directory: &Path) -> DBResult<State> {
+			let components = try!(internals::Components::load(directory));
+			let instances = try!(internals::Instances::load(directory));
 
+			// the indexes only hold what the instances do, so they are rebuilt when they cannot be loaded
+			let (indexes, indexes_changed) = match internals::Indexes::load(directory) {
+				Ok(indexes) => (indexes, false),
+				Err(_) => (internals::Indexes::build(&components, &instances), true)
+			};
+
+			Ok(State {
+				directory: directory.to_path_buf(),
+				resources: Arc::new(try!(internals::Resources::load(directory))),
+				components: Arc::new(components),
+				instances: Arc::new(instances),
+				indexes: Arc::new(indexes),
+				resources_changed: false,
+				components_changed: false,
+				instances_changed: false,
+				indexes_changed: indexes_changed
+			})
+		}
+
+		// Admin functions
+
+		fn add_resource(&mut self, resource_name: &str, resource_type: ResourceIO,	instance_id: Option<usize>) -> DBResult<()> {
+			// the resource must not exist yet
+			if self.resources.resources.contains_key(resource_name) {
+				return Err(DatabaseError::AlreadyDefined(format!("Resource is already defined: {}", resource_name)));
+			}
+			try!(check_route_pattern(resource_name, resource_type));
+
+			// add resource definition
+			let resource_id = self.resources.next_resource_id;
+			{ // mutable scope for the staged resources
+				let resources = Arc::make_mut(&mut self.resources);
+				resources.next_resource_id = resource_id + 1;
+				resources.resources.insert(resource_name.to_string(), (resource_id, resource_type));
+			}
+			if instance_id != None {
+				// to have an instance id, the resource must be FORM IO
+				if resource_type != ResourceIO::FORM {
+					return Err(DatabaseError::MalformedStructure(format!("Resource is not FORM IO type: {}", resource_name)));
+				}
+				try!(self.add_instance(resource_id, instance_id.unwrap()));
+			}
+
+			// add model definition
+			Arc::make_mut(&mut self.resources).models.insert(resource_id, HashMap::new());
+
+			self.resources_changed = true;
+
+			Ok(())
+		}
+
+		fn upsert_resource(&mut self, resource_name: &str, resource_type: ResourceIO,	instance_id: Option<usize>) -> DBResult<()> {
+			let resource_id = match self.resources.resources.get(resource_name) {
+				Some(&(resource_id, _)) => resource_id,
+				None => return self.add_resource(resource_name, resource_type, instance_id)
+			};
+
+			try!(check_route_pattern(resource_name, resource_type));
+
+			// to have instances, the resource must be FORM IO
+			let has_instances = self.resources.resource_instances.get(&resource_id).map_or(false, |instance_ids| !instance_ids.is_empty());
+			if (instance_id != None || has_instances) && resource_type != ResourceIO::FORM {
+				return Err(DatabaseError::MalformedStructure(format!("Resource is not FORM IO type: {}", resource_name)));
+			}
+
+			// static resources have no instances to store data for, inherited components that are
+			// not static are left out of their models
+			if resource_type == ResourceIO::STATIC {
+				let model = self.resources.models.get(&resource_id).unwrap();
+				let mut writable: Vec<&str> = model.iter().filter(|&(_, io_type)| *io_type != DataIO::STATIC).map(|(component_id, _)| &self.components.component_names.get(component_id).unwrap()[..]).collect();
+				if !writable.is_empty() {
+					writable.sort();
+					return Err(DatabaseError::MalformedStructure(format!("Components must be STATIC in static resource {}: {}", resource_name, writable.join(", "))));
+				}
+			}
+
+			// update resource definition, the model and instances are kept
+			Arc::make_mut(&mut self.resources).resources.insert(resource_name.to_string(), (resource_id, resource_type));
+			if let Some(instance_id) = instance_id {
+				try!(self.add_instance(resource_id, instance_id));
+			}
+
+			self.resources_changed = true;
+
+			Ok(())
+		}
+
+		fn add_component(&mut self, component_name: &str, data_type: DataType) -> DBResult<()> {
+			// the component must not exist yet
+			if self.components.components.contains_key(component_name) {
+				return Err(DatabaseError::AlreadyDefined(format!("Component is already defined: {}", component_name)));
+			}
+			try!(self.check_referred_resource(&data_type));
+
+			// add component definition
+			let components = Arc::make_mut(&mut self.components);
+			let component_id = components.next_component_id;
+			components.next_component_id = component_id + 1;
+			components.components.insert(component_name.to_string(), component_id);
+			components.component_names.insert(component_id, component_name.to_string());
+
+			// add type
+			components.component_data_types.insert(component_id, data_type);
+
+			self.components_changed = true;
+
+			Ok(())
+		}
+
+		fn upsert_component(&mut self, component_name: &str, data_type: DataType) -> DBResult<()> {
+			let component_id = match self.components.components.get(component_name) {
+				Some(component_id) => *component_id,
+				None => return self.add_component(component_name, data_type)
+			};
+			try!(self.check_referred_resource(&data_type));
+
+			// the data is kept, so it must fit the new type
+			if let Some(component_instances) = self.instances.instances.get(&component_id) {
+				for (instance_id, data) in component_instances.iter() {
+					let fits = data_type.accepts(data) && match data_type.referred_resource() {
+						Some((referred_resource, _)) => referred_ids(data).into_iter().all(|referred_id| self.has_instance(referred_resource, referred_id).unwrap_or(false)),
+						None => true
+					};
+					if !fits {
+						return Err(DatabaseError::DataTypeMismatch(format!("Component {} cannot be {:?}, instance {} holds {:?}", component_name, data_type, instance_id, data)));
+					}
+				}
+			}
+
+			// update type, the data is kept
+			Arc::make_mut(&mut self.components).component_data_types.insert(component_id, data_type);
+
+			self.components_changed = true;
+
+			Ok(())
+		}
+
+		fn rename_resource(&mut self, resource_name: &str, new_name: &str) -> DBResult<()> {
+			// the resource must exist
+			if !self.resources.resources.contains_key(resource_name) {
+				return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)));
+			}
+			if resource_name == new_name {
+				return Ok(());
+			}
+
+			// the new name must be free
+			if self.resources.resources.contains_key(new_name) {
+				return Err(DatabaseError::AlreadyDefined(format!("Resource is already defined: {}", new_name)));
+			}
+
+			// the instances of a route pattern are keyed by their parameters, which must still fit
+			let (resource_id, resource_type) = *self.resources.resources.get(resource_name).unwrap();
+			try!(check_route_pattern(new_name, resource_type));
+			let has_routes = self.resources.route_instances.get(&resource_id).map_or(false, |routes| !routes.is_empty());
+			if has_routes && try!(route_parameters(resource_name)).len() != try!(route_parameters(new_name)).len() {
+				return Err(DatabaseError::MalformedStructure(format!("Route pattern {} does not have the parameters of: {}", new_name, resource_name)));
+			}
+
+			// move the definition, the id and so the model and data stay the same
+			let resources = Arc::make_mut(&mut self.resources);
+			let definition = resources.resources.remove(resource_name).unwrap();
+			resources.resources.insert(new_name.to_string(), definition);
+
+			self.resources_changed = true;
+
+			// references keep referring to the resource under its new name
+			for data_type in Arc::make_mut(&mut self.components).component_data_types.values_mut() {
+				if rename_referred_resource(data_type, resource_name, new_name) {
+					self.components_changed = true;
+				}
+			}
+
+			// parents are derived from names, so any resource may no longer inherit some components
+			let resource_ids: Vec<usize> = self.resources.resources.values().map(|&(resource_id, _)| resource_id).collect();
+			self.remove_unreachable_data(&resource_ids);
+			self.remove_orphaned_data();
+
+			Ok(())
+		}
+
+		fn rename_component(&mut self, component_name: &str, new_name: &str) -> DBResult<()> {
+			// the component must exist
+			if !self.components.components.contains_key(component_name) {
+				return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)));
+			}
+			if component_name == new_name {
+				return Ok(());
+			}
+
+			// the new name must be free
+			if self.components.components.contains_key(new_name) {
+				return Err(DatabaseError::AlreadyDefined(format!("Component is already defined: {}", new_name)));
+			}
+
+			// move the definition, the id and so the models and data stay the same
+			let components = Arc::make_mut(&mut self.components);
+			let component_id = components.components.remove(component_name).unwrap();
+			components.components.insert(new_name.to_string(), component_id);
+			components.component_names.insert(component_id, new_name.to_string());
+
+			self.components_changed = true;
+
+			Ok(())
+		}
+
+		fn add_component_to_model(&mut self, resource_name: &str, component_name: &str, io_type: DataIO) -> DBResult<()> {
+			// the resource must exist
+			if !self.resources.resources.contains_key(resource_name) {
+				return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)));
+			}
+
+			// the component must exist
+			if !self.components.components.contains_key(component_name) {
+				return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)));
+			}
+
+			let component_id = self.components.components.get(component_name).unwrap();
+			let (resource_id, resource_type) = *self.resources.resources.get(resource_name).unwrap();
+
+			// static resources have no instances to store data for
+			if resource_type == ResourceIO::STATIC && io_type != DataIO::STATIC {
+				return Err(DatabaseError::MalformedStructure(format!("Component {} must be STATIC in static resource: {}", component_name, resource_name)));
+			}
+
+			{ // mutable scope for adding component id to resources
+				let model = Arc::make_mut(&mut self.resources).models.get_mut(&resource_id).unwrap();
+				model.insert(*component_id, io_type);
+			}
+
+			self.resources_changed = true;
+
+			// add component instance to instances (if not static)
+			if io_type != DataIO::STATIC && !self.instances.instances.contains_key(&component_id) {
+				// if no instances defined yet for component, then insert a new one
+				Arc::make_mut(&mut self.instances).instances.insert(*component_id, Arc::new(HashMap::new()));
+				self.instances_changed = true;
+			}
+
+			Ok(())
+		}
+
+		fn set_parent(&mut self, resource_name: &str, parent_name: Option<&str>) -> DBResult<()> {
+			let resource_id = try!(self.resource_id(resource_name));
+			let parent_id = match parent_name {
+				Some(parent_name) => Some(try!(self.resource_id(parent_name))),
+				None => None
+			};
+
+			// the resource must not become its own ancestor
+			if let Some(parent_id) = parent_id {
+				if parent_id == resource_id || self.ancestor_ids(parent_id).contains(&resource_id) {
+					return Err(DatabaseError::MalformedStructure(format!("Resource {} cannot be the parent of its ancestor: {}", parent_name.unwrap(), resource_name)));
+				}
+			}
+
+			Arc::make_mut(&mut self.resources).parents.insert(resource_id, parent_id);
+			self.resources_changed = true;
+
+			// the resource and its descendants may no longer inherit some components
+			let mut resource_ids = self.descendant_ids(resource_id);
+			resource_ids.push(resource_id);
+			self.remove_unreachable_data(&resource_ids);
+			self.remove_orphaned_data();
+
+			Ok(())
+		}
+
+		fn clear_parent(&mut self, resource_name: &str) -> DBResult<()> {
+			let resource_id = try!(self.resource_id(resource_name));
+
+			// the derived parent is a shorter name, but it may have declared this resource as its ancestor
+			let parent = if self.resources.parents.contains_key(&resource_id) {
+				Arc::make_mut(&mut self.resources).parents.remove(&resource_id)
+			} else {
+				None
+			};
+			if let Some(parent_id) = self.parent_id(resource_id) {
+				if self.ancestor_ids(parent_id).contains(&resource_id) {
+					return Err(DatabaseError::MalformedStructure(format!("Resource would become its own ancestor: {}", resource_name)));
+				}
+			}
+			if parent.is_some() {
+				self.resources_changed = true;
+
+				// the resource and its descendants may no longer inherit some components
+				let mut resource_ids = self.descendant_ids(resource_id);
+				resource_ids.push(resource_id);
+				self.remove_unreachable_data(&resource_ids);
+				self.remove_orphaned_data();
+			}
+
+			Ok(())
+		}
+
+		/// Adds an instance id to a resource. Overridden ids are reserved so that they are never
+		/// handed out again. Data is stored by instance id, so an id belongs to one resource only.
+		fn add_instance(&mut self, resource_id: usize, instance_id: usize) -> DBResult<()> {
+			if let Some(owner) = self.instance_resource(instance_id) {
+				if self.resources.resources.get(&owner).unwrap().0 != resource_id {
+					return Err(DatabaseError::AlreadyDefined(format!("Instance {} already belongs to resource: {}", instance_id, owner)));
+				}
+			}
+
+			Arc::make_mut(&mut self.resources).resource_instances.entry(resource_id).or_insert_with(HashSet::new).insert(instance_id);
+			self.resources_changed = true;
+
+			if instance_id >= self.instances.next_instance_id {
+				Arc::make_mut(&mut self.instances).next_instance_id = instance_id + 1;
+				self.instances_changed = true;
+			}
+
+			Ok(())
+		}
+
+		fn create_instance(&mut self, resource_name: &str) -> DBResult<usize> {
+			// the resource must exist and be FORM IO
+			let resource_id = match self.resources.resources.get(resource_name) {
+				Some(&(resource_id, ResourceIO::FORM)) => resource_id,
+				Some(_) => return Err(DatabaseError::MalformedStructure(format!("Resource is not FORM IO type: {}", resource_name))),
+				None => return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)))
+			};
+
+			let instance_id = try!(self.next_instance_id());
+			try!(self.add_instance(resource_id, instance_id));
+
+			Ok(instance_id)
+		}
+
+		fn delete_instance(&mut self, resource_name: &str, instance_id: usize) -> DBResult<()> {
+			try!(self.check_instance(resource_name, instance_id));
+			let resource_id = self.resources.resources.get(resource_name).unwrap().0;
+
+			// restricting references keep the instance from being deleted
+			let references = self.references(resource_name, instance_id);
+			let mut restricting: Vec<String> = Vec::new();
+			for &(component_id, referring_id, on_delete) in references.iter() {
+				if on_delete == OnDelete::RESTRICT {
+					restricting.push(format!("{} of instance {}", self.components.component_names.get(&component_id).unwrap(), referring_id));
+				}
+			}
+			if !restricting.is_empty() {
+				restricting.sort();
+				return Err(DatabaseError::InstanceInUse(format!("Instance {} of resource {} is referred to by: {}", instance_id, resource_name, restricting.join(", "))));
+			}
+
+			// remove the data of every component in the model, including inherited ones
+			let component_ids: Vec<usize> = self.model(resource_id).keys().cloned().collect();
+			for component_id in component_ids {
+				self.remove_data(component_id, instance_id);
+			}
+
+			// the instance no longer belongs to the resource
+			let resources = Arc::make_mut(&mut self.resources);
+			resources.resource_instances.get_mut(&resource_id).unwrap().remove(&instance_id);
+			if let Some(routes) = resources.route_instances.get_mut(&resource_id) {
+				routes.retain(|_, route_instance_id| *route_instance_id != instance_id);
+			}
+			self.resources_changed = true;
+
+			// the data is gone, so a cascade back to this instance finds nothing to do
+			for (component_id, referring_id, on_delete) in references {
+				match on_delete {
+					OnDelete::CASCADE => {
+						// an earlier cascade may have deleted the instance already
+						if let Some(referring_resource) = self.instance_resource(referring_id) {
+							try!(self.delete_instance(&referring_resource, referring_id));
+						}
+					},
+					OnDelete::SET_NULL => {
+						let data = self.instances.instances.get(&component_id).and_then(|component_instances| component_instances.get(&referring_id)).map(|data| data.clone());
+						if let Some(data) = data {
+							self.store_data(component_id, referring_id, remove_reference(data, instance_id));
+						}
+					},
+					OnDelete::RESTRICT => unreachable!()
+				};
+			}
+
+			Ok(())
+		}
+
+		/// Returns the references to an instance of a resource from the data of other instances, as
+		/// the component holding the reference, the referring instance and the action on delete.
+		fn references(&self, resource_name: &str, instance_id: usize) -> Vec<(usize, usize, OnDelete)> {
+			let mut references: Vec<(usize, usize, OnDelete)> = Vec::new();
+			for (component_id, data_type) in self.components.component_data_types.iter() {
+				let on_delete = match data_type.referred_resource() {
+					Some((referred_resource, on_delete)) if referred_resource == resource_name => on_delete,
+					_ => continue
+				};
+				if let Some(component_instances) = self.instances.instances.get(component_id) {
+					for (referring_id, data) in component_instances.iter() {
+						if *referring_id != instance_id && referred_ids(data).contains(&instance_id) {
+							references.push((*component_id, *referring_id, on_delete));
+						}
+					}
+				}
+			}
+
+			references
+		}
+
+		/// Returns the name of the resource an instance belongs to.
+		fn instance_resource(&self, instance_id: usize) -> Option<String> {
+			for (resource_name, &(resource_id, _)) in self.resources.resources.iter() {
+				if self.resources.resource_instances.get(&resource_id).map_or(false, |instance_ids| instance_ids.contains(&instance_id)) {
+					return Some(resource_name.to_string());
+				}
+			}
+
+			None
+		}
+
+		fn remove_component_from_model(&mut self, resource_name: &str, component_name: &str) -> DBResult<()> {
+			let (resource_id, component_id, _) = try!(self.find_model_component(resource_name, component_name));
+
+			// inherited components belong to the model of an ancestor
+			if Arc::make_mut(&mut self.resources).models.get_mut(&resource_id).unwrap().remove(&component_id).is_none() {
+				return Err(DatabaseError::ComponentNotDefined(format!("Component is inherited by resource {}: {}", resource_name, component_name)));
+			}
+			self.resources_changed = true;
+
+			// the resource and its descendants may no longer reach the data
+			let mut resource_ids = self.descendant_ids(resource_id);
+			resource_ids.push(resource_id);
+			self.remove_unreachable_data(&resource_ids);
+			self.remove_orphaned_data();
+
+			Ok(())
+		}
+
+		fn delete_resource(&mut self, resource_name: &str) -> DBResult<()> {
+			// the resource must exist
+			let resource_id = try!(self.resource_id(resource_name));
+
+			// reference components must not be left referring to nothing
+			let mut referring: Vec<String> = Vec::new();
+			for (component_id, data_type) in self.components.component_data_types.iter() {
+				if data_type.referred_resource().map_or(false, |(referred_resource, _)| referred_resource == resource_name) {
+					referring.push(self.components.component_names.get(component_id).unwrap().to_string());
+				}
+			}
+			if !referring.is_empty() {
+				referring.sort();
+				return Err(DatabaseError::ComponentInUse(format!("Resource {} is referred to by: {}", resource_name, referring.join(", "))));
+			}
+			let descendant_ids = self.descendant_ids(resource_id);
+
+			let instance_ids = { // mutable scope for the staged resources
+				let resources = Arc::make_mut(&mut self.resources);
+				resources.resources.remove(resource_name);
+				resources.models.remove(&resource_id);
+				resources.route_instances.remove(&resource_id);
+
+				// children that declared the resource as their parent fall back to their derived parent
+				resources.parents.remove(&resource_id);
+				resources.parents.retain(|_, parent_id| *parent_id != Some(resource_id));
+
+				resources.resource_instances.remove(&resource_id).unwrap_or_else(HashSet::new)
+			};
+			self.resources_changed = true;
+
+			// remove the data of the resource's instances
+			let component_ids: Vec<usize> = self.instances.instances.keys().cloned().collect();
+			for component_id in component_ids {
+				for instance_id in instance_ids.iter() {
+					self.remove_data(component_id, *instance_id);
+				}
+			}
+
+			// the descendants may no longer inherit some of their components
+			self.remove_unreachable_data(&descendant_ids);
+			self.remove_orphaned_data();
+
+			Ok(())
+		}
+
+		fn delete_component(&mut self, component_name: &str, force: bool) -> DBResult<()> {
+			// the component must exist
+			let component_id = match self.components.components.get(component_name) {
+				Some(component_id) => *component_id,
+				None => return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)))
+			};
+
+			// the component may still be part of resource models
+			let mut users: Vec<String> = Vec::new();
+			for (resource_name, &(resource_id, _)) in self.resources.resources.iter() {
+				if self.resources.models.get(&resource_id).map_or(false, |model| model.contains_key(&component_id)) {
+					users.push(resource_name.to_string());
+				}
+			}
+			if !users.is_empty() {
+				if !force {
+					users.sort();
+					return Err(DatabaseError::ComponentInUse(format!("Component {} is used by: {}", component_name, users.join(", "))));
+				}
+				for model in Arc::make_mut(&mut self.resources).models.values_mut() {
+					model.remove(&component_id);
+				}
+				self.resources_changed = true;
+			}
+
+			// remove the component definition and its data
+			let components = Arc::make_mut(&mut self.components);
+			components.components.remove(component_name);
+			components.component_names.remove(&component_id);
+			components.component_data_types.remove(&component_id);
+			if components.component_indexes.remove(&component_id).is_some() {
+				Arc::make_mut(&mut self.indexes).reset(component_id, None);
+				self.indexes_changed = true;
+			}
+			self.components_changed = true;
+			self.remove_orphaned_data();
+
+			Ok(())
+		}
+
+		fn add_index(&mut self, component_name: &str, index_type: IndexType) -> DBResult<()> {
+			// the component must exist and not have an index yet
+			let component_id = match self.components.components.get(component_name) {
+				Some(component_id) => *component_id,
+				None => return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)))
+			};
+			if self.components.component_indexes.contains_key(&component_id) {
+				return Err(DatabaseError::AlreadyDefined(format!("Component is already indexed: {}", component_name)));
+			}
+
+			Arc::make_mut(&mut self.components).component_indexes.insert(component_id, index_type);
+			self.components_changed = true;
+
+			// index the data already stored
+			Arc::make_mut(&mut self.indexes).reset(component_id, Some(index_type));
+			if let Some(component_instances) = self.instances.instances.get(&component_id) {
+				for (instance_id, data) in component_instances.iter() {
+					Arc::make_mut(&mut self.indexes).insert(component_id, *instance_id, data);
+				}
+			}
+			self.indexes_changed = true;
+
+			Ok(())
+		}
+
+		fn remove_index(&mut self, component_name: &str) -> DBResult<()> {
+			// the component must exist and have an index
+			let component_id = match self.components.components.get(component_name) {
+				Some(component_id) => *component_id,
+				None => return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)))
+			};
+			if Arc::make_mut(&mut self.components).component_indexes.remove(&component_id).is_none() {
+				return Err(DatabaseError::IndexNotDefined(format!("Component is not indexed: {}", component_name)));
+			}
+			self.components_changed = true;
+
+			Arc::make_mut(&mut self.indexes).reset(component_id, None);
+			self.indexes_changed = true;
+
+			Ok(())
+		}
+
+		/// Removes the data of components that are no longer stored by any resource model.
+		fn remove_orphaned_data(&mut self) {
+			let mut used: HashSet<usize> = HashSet::new();
+			for model in self.resources.models.values() {
+				for (component_id, io_type) in model.iter() {
+					if *io_type != DataIO::STATIC {
+						used.insert(*component_id);
+					}
+				}
+			}
+
+			let orphaned: Vec<usize> = self.instances.instances.keys().filter(|component_id| !used.contains(component_id)).cloned().collect();
+			for component_id in orphaned {
+				Arc::make_mut(&mut self.instances).instances.remove(&component_id);
+
+				// the index of the component is left empty
+				let index_type = self.components.component_indexes.get(&component_id).cloned();
+				if index_type.is_some() {
+					Arc::make_mut(&mut self.indexes).reset(component_id, index_type);
+					self.indexes_changed = true;
+				}
+				self.instances_changed = true;
+			}
+		}
+
+		/// Removes the data the instances of the given resources hold for components that are no
+		/// longer in their models.
+		fn remove_unreachable_data(&mut self, resource_ids: &[usize]) {
+			let mut unreachable: Vec<(usize, usize)> = Vec::new();
+			for resource_id in resource_ids {
+				let model = self.model(*resource_id);
+				if let Some(instance_ids) = self.resources.resource_instances.get(resource_id) {
+					for component_id in self.instances.instances.keys() {
+						if !model.contains_key(component_id) {
+							for instance_id in instance_ids {
+								unreachable.push((*component_id, *instance_id));
+							}
+						}
+					}
+				}
+			}
+
+			for (component_id, instance_id) in unreachable {
+				self.remove_data(component_id, instance_id);
+			}
+		}
+
+		/// Stores the data of an instance for a component, keeping the index of the component up
+		/// to date. Null removes the data.
+		fn store_data(&mut self, component_id: usize, instance_id: usize, data: Data) {
+			if data == Data::NULL {
+				return self.remove_data(component_id, instance_id);
+			}
+
+			let component_instances = Arc::make_mut(Arc::make_mut(&mut self.instances).instances.entry(component_id).or_insert_with(|| Arc::new(HashMap::new())));
+			if self.components.component_indexes.contains_key(&component_id) {
+				let indexes = Arc::make_mut(&mut self.indexes);
+				if let Some(old) = component_instances.get(&instance_id) {
+					indexes.remove(component_id, instance_id, old);
+				}
+				indexes.insert(component_id, instance_id, &data);
+				self.indexes_changed = true;
+			}
+
+			component_instances.insert(instance_id, data);
+			self.instances_changed = true;
+		}
+
+		/// Removes the data of an instance for a component, along with its index entries.
+		fn remove_data(&mut self, component_id: usize, instance_id: usize) {
+			// the data is only copied to be changed when there is something to remove
+			if !self.instances.instances.get(&component_id).map_or(false, |component_instances| component_instances.contains_key(&instance_id)) {
+				return;
+			}
+
+			let component_instances = Arc::make_mut(Arc::make_mut(&mut self.instances).instances.get_mut(&component_id).unwrap());
+			let old = component_instances.remove(&instance_id).unwrap();
+			if self.components.component_indexes.contains_key(&component_id) {
+				Arc::make_mut(&mut self.indexes).remove(component_id, instance_id, &old);
+				self.indexes_changed = true;
+			}
+			self.instances_changed = true;
+		}
+
+		/// Returns the hashes of every blob that component data refers to.
+		fn blob_hashes(&self) -> HashSet<String> {
+			let mut hashes: HashSet<String> = HashSet::new();
+			for component_instances in self.instances.instances.values() {
+				for data in component_instances.values() {
+					hashes.extend(referred_blobs(data));
+				}
+			}
+
+			hashes
+		}
+
+		// API helper functions
+
+		fn select(&self, resource_name: &str, component_name: &str, instance_id: usize) -> DBResult<Data> {
+			let (_, component_id, _) = try!(self.find_model_component(resource_name, component_name));
+			try!(self.check_instance(resource_name, instance_id));
+
+			// missing data is null, as in load_model
+			match self.instances.instances.get(&component_id).and_then(|component_instances| component_instances.get(&instance_id)) {
+				Some(data) => Ok(data.copy()),
+				None => Ok(Data::NULL)
+			}
+		}
+
+		fn write_component(&mut self, resource_name: &str, component_name: &str, instance_id: usize, data: Tainted, mode: WriteMode) -> DBResult<()> {
+			let (_, component_id, _) = try!(self.find_model_component(resource_name, component_name));
+			let data_type = self.components.component_data_types.get(&component_id).unwrap().clone();
+			let data = try!(parse_tainted(resource_name, component_name, data, data_type));
+
+			self.write_data(resource_name, component_name, instance_id, data, mode)
+		}
+
+		fn write_data(&mut self, resource_name: &str, component_name: &str, instance_id: usize, data: Data, mode: WriteMode) -> DBResult<()> {
+			let (_, component_id, io_type) = try!(self.find_model_component(resource_name, component_name));
+			try!(self.check_instance(resource_name, instance_id));
+
+			// read only and static components are never written
+			if io_type == DataIO::DB_READ_ONLY || io_type == DataIO::STATIC {
+				return Err(DatabaseError::ComponentNotWritable(format!("Component is {:?} in resource {}: {}", io_type, resource_name, component_name)));
+			}
+			try!(self.check_data_type(resource_name, component_name, component_id, &data));
+			let data = self.components.component_data_types.get(&component_id).unwrap().normalize(data);
+
+			let exists = self.instances.instances.get(&component_id).map_or(false, |component_instances| component_instances.contains_key(&instance_id));
+			if mode == WriteMode::INSERT && exists {
+				return Err(DatabaseError::DataAlreadyDefined(format!("Instance already has data for component {}: {}", component_name, instance_id)));
+			}
+			if mode == WriteMode::UPDATE && !exists {
+				return Err(DatabaseError::InstanceNotDefined(format!("Instance has no data for component {}: {}", component_name, instance_id)));
+			}
+
+			// null removes the data
+			self.store_data(component_id, instance_id, data);
+
+			Ok(())
+		}
+
+		fn append_element(&mut self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<()> {
+			let (data_type, element, mut elements) = try!(self.find_elements(resource_name, component_name, instance_id, element));
+			elements.push(element);
+
+			self.write_data(resource_name, component_name, instance_id, collection(&data_type, elements), WriteMode::MERGE)
+		}
+
+		fn remove_element(&mut self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<()> {
+			let (data_type, element, mut elements) = try!(self.find_elements(resource_name, component_name, instance_id, element));
+			let element = index_value(&element);
+			elements.retain(|held| index_value(held) != element);
+
+			self.write_data(resource_name, component_name, instance_id, collection(&data_type, elements), WriteMode::MERGE)
+		}
+
+		fn contains_element(&self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<bool> {
+			let (_, element, elements) = try!(self.find_elements(resource_name, component_name, instance_id, element));
+			let element = index_value(&element);
+
+			Ok(elements.iter().any(|held| index_value(held) == element))
+		}
+
+		/// Parses an element for the list or set component of a resource. Returns the component
+		/// data type, the element and the elements the instance holds now.
+		fn find_elements(&self, resource_name: &str, component_name: &str, instance_id: usize, element: Tainted) -> DBResult<(DataType, Data, Vec<Data>)> {
+			let (_, component_id, _) = try!(self.find_model_component(resource_name, component_name));
+			try!(self.check_instance(resource_name, instance_id));
+			let data_type = try!(self.check_collection(resource_name, component_name, component_id));
+
+			let element = match try!(parse_tainted(resource_name, component_name, element, data_type.element_type().unwrap().clone())) {
+				Data::NULL => return Err(DatabaseError::MalformedData(format!("Component {} of resource {}: An element cannot be null", component_name, resource_name))),
+				element => element
+			};
+			let elements = match self.instances.instances.get(&component_id).and_then(|component_instances| component_instances.get(&instance_id)) {
+				Some(&Data::LIST(ref elements)) | Some(&Data::SET(ref elements)) => elements.clone(),
+				_ => Vec::new()
+			};
+
+			Ok((data_type, element, elements))
+		}
+
+		/// Checks that a component is a list or set and returns its data type.
+		fn check_collection(&self, resource_name: &str, component_name: &str, component_id: usize) -> DBResult<DataType> {
+			let data_type = self.components.component_data_types.get(&component_id).unwrap();
+			match data_type.element_type() {
+				Some(_) => Ok(data_type.clone()),
+				None => Err(DatabaseError::DataTypeMismatch(format!("Component {} of resource {} is {:?}, not a list or set", component_name, resource_name, data_type)))
+			}
+		}
+
+		fn next_instance_id(&mut self) -> DBResult<usize> {
+			let instance_id = self.instances.next_instance_id;
+			Arc::make_mut(&mut self.instances).next_instance_id = self.instances.next_instance_id + 1;
+			self.instances_changed = true;
+
+			Ok(instance_id)
+		}
+
+		// API functions
+
+		fn get_component_data_type(&self, component_name: &str) -> DBResult<DataType> {
+			// the component must exist
+			if !self.components.components.contains_key(component_name) {
+				return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)));
+			}
+
+			// get component id
+			let component_id = self.components.components.get(component_name).unwrap();
+
+			// get type
+			let data_type: &DataType = self.components.component_data_types.get(&component_id).unwrap();
+
+			// ?? done with Copy/Clone? Without Copy/Clone you cannot move this out of scope
+			Ok(data_type.clone())
+		}
+
+		fn is_static_resource(&self, resource_name: &str) -> DBResult<bool> {
+
+			// the resource must exist
+			if !self.resources.resources.contains_key(resource_name) {
+				return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)));
+			}
+
+			// get static flag
+			let static_flag = ResourceIO::STATIC == self.resources.resources.get(resource_name).unwrap().1;
+
+			Ok(static_flag)
+		}
+
+		fn load_static_model(&self, resource_name: &str) -> DBResult<HashMap<String, ComponentInstance>> {
+
+			// the resource must exist
+			if !self.resources.resources.contains_key(resource_name) {
+				return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)));
+			}
+
+			// the resource must be static
+			if !try!(self.is_static_resource(resource_name)) {
+				return Err(DatabaseError::MalformedStructure(format!("Resource is not static: {}", resource_name)));
+			}
+
+			// get resource id
+			let resource_id = self.resources.resources.get(resource_name).unwrap().0;
+
+			// get model
+			let mut model: HashMap<String, ComponentInstance> = HashMap::new();
+			let model_component = self.model(resource_id);
+			for (component_id, io_type) in model_component.iter() {
+				// get component data
+				let component_name = self.components.component_names.get(&component_id).unwrap();
+				let component_data_type = self.components.component_data_types.get(&component_id).unwrap();
+
+				// the component must be static
+				if *io_type != DataIO::STATIC {
+					return Err(DatabaseError::MalformedStructure(format!("Component is not static: {}", resource_name)));
+				}
+
+				let instance = ComponentInstance {
+					component_id: *component_id,
+					component_name: component_name.to_string(),
+					component_data_type: component_data_type.clone(),
+					component_io_type: *io_type,
+					data: Data::NULL
+				};
+
+				model.insert(component_name.to_string(), instance);
+			}
+
+			Ok(model)
+		}
+
+		fn load_model(&self, resource_name: &str, instance_id: usize) -> DBResult<HashMap<String, ComponentInstance>> {
+
+			// the resource must exist
+			if !self.resources.resources.contains_key(resource_name) {
+				return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)));
+			}
+
+			// get resource id
+			let (resource_id, resource_type) = *self.resources.resources.get(resource_name).unwrap();
+
+			// static resources have no instances
+			if resource_type != ResourceIO::STATIC {
+				try!(self.check_instance(resource_name, instance_id));
+			}
+
+			// get model
+			let mut model: HashMap<String, ComponentInstance> = HashMap::new();
+			let model_components = self.model(resource_id);
+			for (component_id, io_type) in model_components.iter() {
+				// get component data
+				let component_name = self.components.component_names.get(&component_id).unwrap();
+				let component_data_type = self.components.component_data_types.get(&component_id).unwrap();
+
+				// get data, a static resource owns no instance to read it for
+				let data: Data = match self.instances.instances.get(&component_id).and_then(|component_instances| component_instances.get(&instance_id)) {
+					Some(d) if resource_type != ResourceIO::STATIC => d.copy(),
+					_ => Data::NULL
+				};
+
+				let instance = ComponentInstance {
+					component_id: *component_id,
+					component_name: component_name.to_string(),
+					component_data_type: component_data_type.clone(),
+					component_io_type: *io_type,
+					data: data
+				};
+			
+				model.insert(component_name.to_string(), instance);
+			}
+
+			Ok(model)
+		}
+
+		fn save_model(&mut self, model: HashMap<String, ComponentInstance>, resource_name: &str, instance_id: usize) -> DBResult<Vec<String>> {
+			// the resource must exist
+			let (resource_id, resource_type) = match self.resources.resources.get(resource_name) {
+				Some(&(resource_id, resource_type)) => (resource_id, resource_type),
+				None => return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)))
+			};
+
+			// static resources have no instances
+			if resource_type != ResourceIO::STATIC {
+				try!(self.check_instance(resource_name, instance_id));
+			}
+
+			let resource_model = self.model(resource_id);
+			let mut writes: Vec<(usize, Data)> = Vec::new();
+			let mut skipped: Vec<String> = Vec::new();
+			for (component_name, instance) in model {
+				let component_id = instance.component_id;
+
+				// the component must exist and match the name it was submitted under
+				match self.components.component_names.get(&component_id) {
+					Some(name) if *name == component_name => (),
+					Some(name) => return Err(DatabaseError::MalformedStructure(format!("Component id {} is {}, not {}", component_id, name, component_name))),
+					None => return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)))
+				};
+
+				// the component must be part of the resource model, which also defines its io type
+				let component_io_type = match resource_model.get(&component_id) {
+					Some(io_type) => *io_type,
+					None => return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined in model of resource {}: {}", resource_name, component_name)))
+				};
+				if component_io_type == DataIO::DB_READ_ONLY || component_io_type == DataIO::STATIC {
+					// do not try to save any read only or static components
+					skipped.push(component_name);
+					continue;
+				}
+
+				// the data must fit the component data type
+				try!(self.check_data_type(resource_name, &component_name, component_id, &instance.data));
+
+				let data = self.components.component_data_types.get(&component_id).unwrap().normalize(instance.data);
+				writes.push((component_id, data));
+			}
+
+			// everything checked out, so apply all writes at once
+			for (component_id, data) in writes {
+				self.store_data(component_id, instance_id, data);
+			}
+
+			skipped.sort();
+			Ok(skipped)
+		}
+
+		fn match_route(&self, path: &str) -> DBResult<Route> {
+			// braces only belong in patterns, a request path spelling one out must not match it
+			if path.contains(|c| c == '{' || c == '}') {
+				return Err(DatabaseError::MalformedData(format!("Request path cannot contain braces: {}", path)));
+			}
+
+			// a resource with the exact name always handles the path
+			if self.resources.resources.contains_key(path) && try!(route_parameters(path)).is_empty() {
+				return Ok(Route {
+					resource_name: path.to_string(),
+					instance_id: None,
+					params: HashMap::new()
+				});
+			}
+
+			// otherwise the route pattern with the most literal segments wins, so "/blog/{username}/about/"
+			// handles "/blog/alice/about/" before "/blog/{username}/{slug}/" does
+			let mut best: Option<(usize, &str, Vec<String>, Vec<String>)> = None;
+			for resource_name in self.resources.resources.keys() {
+				let parameters = try!(route_parameters(resource_name));
+				if parameters.is_empty() {
+					continue;
+				}
+				if let Some(values) = match_route_pattern(resource_name, path) {
+					let literals = resource_name.split('/').count() - parameters.len();
+					let better = match best {
+						Some((best_literals, best_name, _, _)) => literals > best_literals || (literals == best_literals && &resource_name[..] < best_name),
+						None => true
+					};
+					if better {
+						best = Some((literals, resource_name, parameters, values));
+					}
+				}
+			}
+
+			let (_, resource_name, parameters, values) = match best {
+				Some(best) => best,
+				None => return Err(DatabaseError::ResourceNotDefined(format!("No resource handles path: {}", path)))
+			};
+			let resource_id = self.resources.resources.get(resource_name).unwrap().0;
+			let instance_id = self.resources.route_instances.get(&resource_id).and_then(|routes| routes.get(&values)).cloned();
+
+			Ok(Route {
+				resource_name: resource_name.to_string(),
+				instance_id: instance_id,
+				params: parameters.into_iter().zip(values.into_iter()).collect()
+			})
+		}
+
+		fn resolve_route(&mut self, path: &str) -> DBResult<Route> {
+			let mut route = try!(self.match_route(path));
+			if route.instance_id.is_some() || route.params.is_empty() {
+				return Ok(route);
+			}
+
+			// first time the parameters are seen, so they get a new instance
+			let resource_id = try!(self.resource_id(&route.resource_name));
+			let values: Vec<String> = {
+				let parameters = try!(route_parameters(&route.resource_name));
+				parameters.iter().map(|parameter| route.params.get(parameter).unwrap().to_string()).collect()
+			};
+			let instance_id = try!(self.create_instance(&route.resource_name));
+			Arc::make_mut(&mut self.resources).route_instances.entry(resource_id).or_insert_with(HashMap::new).insert(values, instance_id);
+			self.resources_changed = true;
+
+			route.instance_id = Some(instance_id);
+			Ok(route)
+		}
+
+		// Introspection functions
+
+		fn list_resources(&self) -> DBResult<Vec<ResourceInfo>> {
+			let mut resources: Vec<ResourceInfo> = Vec::new();
+			for (resource_name, &(_, resource_type)) in self.resources.resources.iter() {
+				let instance_ids = try!(self.list_instances(resource_name));
+
+				resources.push(ResourceInfo {
+					resource_name: resource_name.to_string(),
+					resource_io_type: resource_type,
+					instance_ids: instance_ids
+				});
+			}
+
+			resources.sort_by(|a, b| a.resource_name.cmp(&b.resource_name));
+			Ok(resources)
+		}
+
+		fn list_components(&self) -> DBResult<Vec<ComponentInfo>> {
+			let mut components: Vec<ComponentInfo> = Vec::new();
+			for (component_name, component_id) in self.components.components.iter() {
+				components.push(ComponentInfo {
+					component_name: component_name.to_string(),
+					component_data_type: self.components.component_data_types.get(component_id).unwrap().clone()
+				});
+			}
+
+			components.sort_by(|a, b| a.component_name.cmp(&b.component_name));
+			Ok(components)
+		}
+
+		fn describe_model(&self, resource_name: &str) -> DBResult<HashMap<String, DataIO>> {
+			let resource_id = try!(self.resource_id(resource_name));
+
+			let mut model: HashMap<String, DataIO> = HashMap::new();
+			for (component_id, io_type) in self.model(resource_id).iter() {
+				let component_name = self.components.component_names.get(component_id).unwrap();
+				model.insert(component_name.to_string(), *io_type);
+			}
+
+			Ok(model)
+		}
+
+		fn list_instances(&self, resource_name: &str) -> DBResult<Vec<usize>> {
+			let resource_id = try!(self.resource_id(resource_name));
+
+			let mut instance_ids: Vec<usize> = match self.resources.resource_instances.get(&resource_id) {
+				Some(instance_ids) => instance_ids.iter().cloned().collect(),
+				None => Vec::new()
+			};
+			instance_ids.sort();
+			Ok(instance_ids)
+		}
+
+		fn has_instance(&self, resource_name: &str, instance_id: usize) -> DBResult<bool> {
+			let resource_id = try!(self.resource_id(resource_name));
+
+			Ok(self.resources.resource_instances.get(&resource_id).map_or(false, |instance_ids| instance_ids.contains(&instance_id)))
+		}
+
+		fn instances_with_data(&self, resource_name: &str) -> DBResult<Vec<usize>> {
+			let resource_id = try!(self.resource_id(resource_name));
+			let own_instance_ids = match self.resources.resource_instances.get(&resource_id) {
+				Some(own_instance_ids) => own_instance_ids,
+				None => return Ok(Vec::new())
+			};
+
+			// component data is shared by every resource with the component, so only the
+			// resource's own instances count
+			let mut instance_ids: HashSet<usize> = HashSet::new();
+			for component_id in self.model(resource_id).keys() {
+				if let Some(component_instances) = self.instances.instances.get(component_id) {
+					instance_ids.extend(component_instances.keys().filter(|instance_id| own_instance_ids.contains(instance_id)).cloned());
+				}
+			}
+
+			let mut instance_ids: Vec<usize> = instance_ids.into_iter().collect();
+			instance_ids.sort();
+			Ok(instance_ids)
+		}
+
+		fn resources_with_component(&self, component_name: &str) -> DBResult<Vec<String>> {
+			let component_id = match self.components.components.get(component_name) {
+				Some(component_id) => *component_id,
+				None => return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)))
+			};
+
+			let mut resource_names: Vec<String> = Vec::new();
+			for (resource_name, &(resource_id, _)) in self.resources.resources.iter() {
+				if self.model(resource_id).contains_key(&component_id) {
+					resource_names.push(resource_name.to_string());
+				}
+			}
+
+			resource_names.sort();
+			Ok(resource_names)
+		}
+
+		fn shared_components(&self) -> DBResult<Vec<SharedComponent>> {
+			let mut shared: Vec<SharedComponent> = Vec::new();
+			for component_name in self.components.components.keys() {
+				let resource_names = try!(self.resources_with_component(component_name));
+				if resource_names.len() > 1 {
+					shared.push(SharedComponent {
+						component_name: component_name.to_string(),
+						resource_names: resource_names
+					});
+				}
+			}
+
+			shared.sort_by(|a, b| a.component_name.cmp(&b.component_name));
+			Ok(shared)
+		}
+
+		fn instances_with_value(&self, component_name: &str, value: &Data) -> DBResult<Vec<ResourceInstance>> {
+			let resource_names = try!(self.resources_with_component(component_name));
+			let component_id = self.components.components.get(component_name).unwrap();
+			let component_instances = match self.instances.instances.get(component_id) {
+				Some(component_instances) => component_instances,
+				None => return Ok(Vec::new())
+			};
+
+			try!(check_search_value(component_name, value));
+			let value = &index_value(value);
+
+			// an index knows the instances holding the value without looking at their data
+			let indexed: Option<HashSet<usize>> = self.indexes.lookup(*component_id, value).map(|instance_ids| instance_ids.into_iter().collect());
+
+			// resource names are sorted already
+			let mut found: Vec<ResourceInstance> = Vec::new();
+			for resource_name in resource_names {
+				let instance_ids = try!(self.list_instances(&resource_name));
+				for instance_id in instance_ids {
+					let holds = match (indexed.as_ref(), component_instances.get(&instance_id)) {
+						(Some(indexed), _) => indexed.contains(&instance_id),
+						(None, Some(&Data::LIST(ref elements))) | (None, Some(&Data::SET(ref elements))) => elements.iter().any(|element| index_value(element) == *value),
+						(None, Some(data)) => index_value(data) == *value,
+						(None, None) => false
+					};
+					if holds {
+						found.push(ResourceInstance {
+							resource_name: resource_name.to_string(),
+							instance_id: instance_id
+						});
+					}
+				}
+			}
+
+			Ok(found)
+		}
+
+		fn instances_in_range(&self, component_name: &str, from: Option<&Data>, to: Option<&Data>) -> DBResult<Vec<ResourceInstance>> {
+			let component_id = match self.components.components.get(component_name) {
+				Some(component_id) => *component_id,
+				None => return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)))
+			};
+			for bound in from.iter().chain(to.iter()) {
+				try!(check_search_value(component_name, bound));
+			}
+			let instance_ids = match self.indexes.range(component_id, from, to) {
+				Some(instance_ids) => instance_ids,
+				None => return Err(DatabaseError::IndexNotDefined(format!("Component does not have an ordered index: {}", component_name)))
+			};
+
+			let mut found: Vec<ResourceInstance> = Vec::new();
+			for instance_id in instance_ids {
+				if let Some(resource_name) = self.instance_resource(instance_id) {
+					found.push(ResourceInstance {
+						resource_name: resource_name,
+						instance_id: instance_id
+					});
+				}
+			}
+
+			Ok(found)
+		}
+
+		fn traverse_references(&self, resource_name: &str, instance_id: usize, direction: Direction, max_depth: Option<usize>) -> DBResult<Vec<ReachedInstance>> {
+			try!(self.check_instance(resource_name, instance_id));
+
+			let mut reached: Vec<ReachedInstance> = Vec::new();
+			let mut visited: HashSet<usize> = HashSet::new();
+			visited.insert(instance_id);
+
+			// follow the references one depth at a time, so every instance is reached by the fewest
+			let mut current: Vec<(String, usize)> = vec![(resource_name.to_string(), instance_id)];
+			let mut depth = 0;
+			while !current.is_empty() && max_depth.map_or(true, |max_depth| depth < max_depth) {
+				depth = depth + 1;
+
+				let mut next: Vec<ReachedInstance> = Vec::new();
+				for (resource_name, instance_id) in current {
+					for (next_resource, next_id, component_id) in self.linked_instances(&resource_name, instance_id, direction) {
+						next.push(ReachedInstance {
+							resource_name: next_resource,
+							instance_id: next_id,
+							component_name: self.components.component_names.get(&component_id).unwrap().to_string(),
+							depth: depth
+						});
+					}
+				}
+				next.sort_by(|a, b| (&a.resource_name, a.instance_id, &a.component_name).cmp(&(&b.resource_name, b.instance_id, &b.component_name)));
+
+				current = Vec::new();
+				for instance in next {
+					if visited.insert(instance.instance_id) {
+						current.push((instance.resource_name.to_string(), instance.instance_id));
+						reached.push(instance);
+					}
+				}
+			}
+
+			Ok(reached)
+		}
+
+		/// Returns the instances an instance of a resource refers to, or that refer to it, as the
+		/// resource name, the instance id and the reference component linking them.
+		fn linked_instances(&self, resource_name: &str, instance_id: usize, direction: Direction) -> Vec<(String, usize, usize)> {
+			let mut linked: Vec<(String, usize, usize)> = Vec::new();
+			match direction {
+				Direction::OUTGOING => {
+					let resource_id = self.resources.resources.get(resource_name).unwrap().0;
+					for component_id in self.model(resource_id).keys() {
+						let referred_resource = match self.components.component_data_types.get(component_id).unwrap().referred_resource() {
+							Some((referred_resource, _)) => referred_resource,
+							None => continue
+						};
+						if let Some(data) = self.instances.instances.get(component_id).and_then(|component_instances| component_instances.get(&instance_id)) {
+							for referred_id in referred_ids(data) {
+								linked.push((referred_resource.to_string(), referred_id, *component_id));
+							}
+						}
+					}
+				},
+				Direction::INCOMING => {
+					for (component_id, referring_id, _) in self.references(resource_name, instance_id) {
+						if let Some(referring_resource) = self.instance_resource(referring_id) {
+							linked.push((referring_resource, referring_id, component_id));
+						}
+					}
+				}
+			};
+
+			linked
+		}
+
+		fn parent(&self, resource_name: &str) -> DBResult<Option<String>> {
+			let resource_id = try!(self.resource_id(resource_name));
+
+			Ok(self.parent_id(resource_id).map(|parent_id| self.resource_name(parent_id).to_string()))
+		}
+
+		fn children(&self, resource_name: &str) -> DBResult<Vec<String>> {
+			let resource_id = try!(self.resource_id(resource_name));
+
+			Ok(self.child_ids(resource_id).into_iter().map(|child_id| self.resource_name(child_id).to_string()).collect())
+		}
+
+		fn ancestors(&self, resource_name: &str) -> DBResult<Vec<String>> {
+			let resource_id = try!(self.resource_id(resource_name));
+
+			Ok(self.ancestor_ids(resource_id).into_iter().map(|ancestor_id| self.resource_name(ancestor_id).to_string()).collect())
+		}
+
+		fn descendants(&self, resource_name: &str) -> DBResult<Vec<String>> {
+			let resource_id = try!(self.resource_id(resource_name));
+
+			Ok(self.descendant_ids(resource_id).into_iter().map(|descendant_id| self.resource_name(descendant_id).to_string()).collect())
+		}
+
+		/// Returns the model of a resource including inherited components. The model of a resource
+		/// overrides the io types of the components it inherits. A static resource has no instances
+		/// to store data for, so it only inherits static components.
+		fn model(&self, resource_id: usize) -> HashMap<usize, DataIO> {
+			let mut model: HashMap<usize, DataIO> = HashMap::new();
+			let mut lineage = self.ancestor_ids(resource_id);
+			lineage.reverse();
+			for model_resource_id in lineage {
+				if let Some(resource_model) = self.resources.models.get(&model_resource_id) {
+					for (component_id, io_type) in resource_model.iter() {
+						model.insert(*component_id, *io_type);
+					}
+				}
+			}
+			if self.resources.resources.values().any(|&(id, resource_type)| id == resource_id && resource_type == ResourceIO::STATIC) {
+				model.retain(|_, io_type| *io_type == DataIO::STATIC);
+			}
+			if let Some(resource_model) = self.resources.models.get(&resource_id) {
+				for (component_id, io_type) in resource_model.iter() {
+					model.insert(*component_id, *io_type);
+				}
+			}
+
+			model
+		}
+
+		/// Returns the declared parent of a resource or, without one, the resource with the longest
+		/// name that is a path prefix of its name.
+		fn parent_id(&self, resource_id: usize) -> Option<usize> {
+			if let Some(parent_id) = self.resources.parents.get(&resource_id) {
+				return *parent_id;
+			}
+
+			let resource_name = self.resource_name(resource_id);
+			let mut parent: Option<(&str, usize)> = None;
+			for (name, &(id, _)) in self.resources.resources.iter() {
+				if name.len() < resource_name.len() && name.ends_with('/') && resource_name.starts_with(&name[..]) {
+					if parent.map_or(true, |(parent_name, _)| name.len() > parent_name.len()) {
+						parent = Some((name, id));
+					}
+				}
+			}
+
+			parent.map(|(_, id)| id)
+		}
+
+		/// Returns the ancestors of a resource, nearest first. Stops at a cycle, which declared
+		/// parents combined with renamed resources can still produce.
+		fn ancestor_ids(&self, resource_id: usize) -> Vec<usize> {
+			let mut ancestor_ids: Vec<usize> = Vec::new();
+			let mut current = self.parent_id(resource_id);
+			while let Some(ancestor_id) = current {
+				if ancestor_id == resource_id || ancestor_ids.contains(&ancestor_id) {
+					break;
+				}
+				ancestor_ids.push(ancestor_id);
+				current = self.parent_id(ancestor_id);
+			}
+
+			ancestor_ids
+		}
+
+		/// Returns the children of a resource, sorted by name.
+		fn child_ids(&self, resource_id: usize) -> Vec<usize> {
+			let mut children: Vec<(&str, usize)> = Vec::new();
+			for (name, &(id, _)) in self.resources.resources.iter() {
+				if id != resource_id && self.parent_id(id) == Some(resource_id) {
+					children.push((name, id));
+				}
+			}
+
+			children.sort();
+			children.into_iter().map(|(_, id)| id).collect()
+		}
+
+		/// Returns the descendants of a resource, depth first with children sorted by name.
+		fn descendant_ids(&self, resource_id: usize) -> Vec<usize> {
+			let mut descendant_ids: Vec<usize> = Vec::new();
+			let mut stack: Vec<usize> = self.child_ids(resource_id);
+			stack.reverse();
+			while let Some(descendant_id) = stack.pop() {
+				if descendant_id == resource_id || descendant_ids.contains(&descendant_id) {
+					continue;
+				}
+				descendant_ids.push(descendant_id);
+
+				let mut children = self.child_ids(descendant_id);
+				children.reverse();
+				stack.extend(children);
+			}
+
+			descendant_ids
+		}
+
+		fn resource_name(&self, resource_id: usize) -> &str {
+			self.resources.resources.iter().find(|&(_, &(id, _))| id == resource_id).map(|(name, _)| &name[..]).unwrap()
+		}
+
+		fn resource_id(&self, resource_name: &str) -> DBResult<usize> {
+			match self.resources.resources.get(resource_name) {
+				Some(&(resource_id, _)) => Ok(resource_id),
+				None => Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)))
+			}
+		}
+
+		/// Looks up a component in the model of a resource. Returns the resource id, the component
+		/// id and the io type of the component in that model.
+		fn find_model_component(&self, resource_name: &str, component_name: &str) -> DBResult<(usize, usize, DataIO)> {
+			// the resource must exist
+			let resource_id = match self.resources.resources.get(resource_name) {
+				Some(&(resource_id, _)) => resource_id,
+				None => return Err(DatabaseError::ResourceNotDefined(format!("Resource is not defined: {}", resource_name)))
+			};
+
+			// the component must exist
+			let component_id = match self.components.components.get(component_name) {
+				Some(component_id) => *component_id,
+				None => return Err(DatabaseError::ComponentNotDefined(format!("Component is not defined: {}", component_name)))
+			};
+
+			// the component must be part of the resource model, inherited or not
+			match self.model(resource_id).get(&component_id) {
+				Some(io_type) => Ok((resource_id, component_id, *io_type)),
+				None => Err(DatabaseError::ComponentNotDefined(format!("Component is not defined in model of resource {}: {}", resource_name, component_name)))
+			}
+		}
+
+		/// Checks that data can be stored in a component of a resource. References must refer to
+		/// instances of the resource the component refers to, and blobs must have a blob file.
+		fn check_data_type(&self, resource_name: &str, component_name: &str, component_id: usize, data: &Data) -> DBResult<()> {
+			let data_type = self.components.component_data_types.get(&component_id).unwrap();
+			if data_type.accepts(data) {
+				// NaN is not ordered, so it could not be indexed or compared
+				if has_nan(data) {
+					return Err(DatabaseError::MalformedData(format!("Component {} of resource {}: A float cannot be NaN", component_name, resource_name)));
+				}
+				if let Some((referred_resource, _)) = data_type.referred_resource() {
+					for instance_id in referred_ids(data) {
+						if !try!(self.has_instance(referred_resource, instance_id)) {
+							return Err(DatabaseError::InstanceNotDefined(format!("Component {} of resource {} refers to an instance that is not defined for resource {}: {}", component_name, resource_name, referred_resource, instance_id)));
+						}
+					}
+				}
+				// removing unused blobs takes the writer lock too, so a blob that exists now is kept
+				// until the data referring to it is saved
+				for hash in referred_blobs(data) {
+					if !internals::has_blob(&self.directory, &hash) {
+						return Err(DatabaseError::FileNotFound(format!("Component {} of resource {} refers to a blob that is not stored: {}", component_name, resource_name, hash)));
+					}
+				}
+				Ok(())
+			} else {
+				// an empty collection has no data type of its own
+				let found = match data.data_type() {
+					Some(found) => format!("{:?}", found),
+					None => format!("{:?}", data)
+				};
+				Err(DatabaseError::DataTypeMismatch(format!("Component {} of resource {} is {:?}, not {}", component_name, resource_name, data_type, found)))
+			}
+		}
+
+		/// Checks that the resource a component data type refers to exists.
+		fn check_referred_resource(&self, data_type: &DataType) -> DBResult<()> {
+			match data_type.referred_resource() {
+				Some((referred_resource, _)) => self.resource_id(referred_resource).map(|_| ()),
+				None => Ok(())
+			}
+		}
+
+		/// Checks that an instance belongs to a resource.
+		fn check_instance(&self, resource_name: &str, instance_id: usize) -> DBResult<()> {
+			if try!(self.has_instance(resource_name, instance_id)) {
+				Ok(())
+			} else {
+				Err(DatabaseError::InstanceNotDefined(format!("Instance is not defined for resource {}: {}", resource_name, instance_id)))
+			}
+		}
+	}
+
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	enum WriteMode {
+		/// The instance must not have data for the component yet.
+		INSERT,
+		/// The instance must already have data for the component.
+		UPDATE,
+		/// The instance may or may not have data for the component.
+		MERGE
+	}
+
+	/// Parses tainted data into a data type for a component of a resource, naming them in errors.
+	fn parse_tainted(resource_name: &str, component_name: &str, data: Tainted, data_type: DataType) -> DBResult<Data> {
+		// data only leaves Tainted through its filter
+		match data.parse(data_type) {
+			Ok(data) => Ok(data),
+			Err(DatabaseError::MalformedData(message)) => Err(DatabaseError::MalformedData(format!("Component {} of resource {}: {}", component_name, resource_name, message))),
+			Err(DatabaseError::DataOutOfRange(message)) => Err(DatabaseError::DataOutOfRange(format!("Component {} of resource {}: {}", component_name, resource_name, message))),
+			Err(error) => Err(error)
+		}
+	}
+
+	/// Builds the collection a list or set component stores.
+	fn collection(data_type: &DataType, elements: Vec<Data>) -> Data {
+		match data_type {
+			&DataType::SET(_) => Data::SET(elements),
+			_ => Data::LIST(elements)
+		}
+	}
+
+	/// Returns the instance ids that reference data refers to.
+	fn referred_ids(data: &Data) -> Vec<usize> {
+		match data {
+			&Data::REFERENCE(instance_id) => vec![instance_id],
+			&Data::LIST(ref elements) | &Data::SET(ref elements) => elements.iter().flat_map(referred_ids).collect(),
+			_ => Vec::new()
+		}
+	}
+
+	/// Returns whether data is, or holds, a float that is NaN.
+	fn has_nan(data: &Data) -> bool {
+		match data {
+			&Data::FLOAT_32(d) => d.is_nan(),
+			&Data::FLOAT_64(d) => d.is_nan(),
+			&Data::LIST(ref elements) | &Data::SET(ref elements) => elements.iter().any(has_nan),
+			_ => false
+		}
+	}
+
+	/// Brings a value into the form it is indexed and searched by. Integers of every width are
+	/// widened to 64 bits, since a UINT or INT component holds any of them, and -0.0 becomes 0.0,
+	/// which it compares equal to.
+	fn index_value(data: &Data) -> Data {
+		match data {
+			&Data::FLOAT_32(d) if d == 0.0 => Data::FLOAT_32(0.0),
+			&Data::FLOAT_64(d) if d == 0.0 => Data::FLOAT_64(0.0),
+			&Data::SIGNED_INT_8(d) => Data::SIGNED_INT_64(d as i64),
+			&Data::SIGNED_INT_16(d) => Data::SIGNED_INT_64(d as i64),
+			&Data::SIGNED_INT_32(d) => Data::SIGNED_INT_64(d as i64),
+			&Data::UNSIGNED_INT_8(d) => Data::UNSIGNED_INT_64(d as u64),
+			&Data::UNSIGNED_INT_16(d) => Data::UNSIGNED_INT_64(d as u64),
+			&Data::UNSIGNED_INT_32(d) => Data::UNSIGNED_INT_64(d as u64),
+			data => data.clone()
+		}
+	}
+
+	/// Checks that a value can be searched for.
+	fn check_search_value(component_name: &str, value: &Data) -> DBResult<()> {
+		if has_nan(value) {
+			return Err(DatabaseError::MalformedData(format!("Component {}: Cannot search for NaN", component_name)));
+		}
+		Ok(())
+	}
+
+	/// Returns the hashes of the blobs that data refers to.
+	fn referred_blobs(data: &Data) -> Vec<String> {
+		match data {
+			&Data::BLOB(ref blob) => vec![blob.hash.to_string()],
+			&Data::LIST(ref elements) | &Data::SET(ref elements) => elements.iter().flat_map(referred_blobs).collect(),
+			_ => Vec::new()
+		}
+	}
+
+	/// Removes the references to an instance from reference data.
+	fn remove_reference(data: Data, instance_id: usize) -> Data {
+		match data {
+			Data::REFERENCE(referred_id) if referred_id == instance_id => Data::NULL,
+			Data::LIST(elements) => Data::LIST(elements.into_iter().filter(|element| *element != Data::REFERENCE(instance_id)).collect()),
+			Data::SET(elements) => Data::SET(elements.into_iter().filter(|element| *element != Data::REFERENCE(instance_id)).collect()),
+			data => data
+		}
+	}
+
+	/// Points a data type that refers to a resource at its new name. Returns whether it changed.
+	fn rename_referred_resource(data_type: &mut DataType, resource_name: &str, new_name: &str) -> bool {
+		match data_type {
+			&mut DataType::REFERENCE(ref mut referred_resource, _) if referred_resource == resource_name => {
+				*referred_resource = new_name.to_string();
+				true
+			},
+			&mut DataType::LIST(ref mut element_type) | &mut DataType::SET(ref mut element_type) => rename_referred_resource(element_type, resource_name, new_name),
+			_ => false
+		}
+	}
+
+	/// Returns the names of the parameters of a route pattern such as "/blog/{username}/{slug}/",
+	/// in order. A parameter must take up a whole path segment and appear only once. A resource
+	/// name without parameters is not a route pattern.
+	fn route_parameters(resource_name: &str) -> DBResult<Vec<String>> {
+		let mut parameters: Vec<String> = Vec::new();
+
+		// this runs for every resource on every route match, so segments are checked by hand
+		if !resource_name.contains('{') && !resource_name.contains('}') {
+			return Ok(parameters);
+		}
+
+		for segment in resource_name.split('/') {
+			if !segment.contains('{') && !segment.contains('}') {
+				continue;
+			}
+
+			// a parameter name is ascii letters, digits and underscores
+			let name = segment.trim_start_matches('{').trim_end_matches('}');
+			if segment.len() != name.len() + 2 || !segment.starts_with('{') || name.is_empty() || !name.chars().all(|c| c.is_digit(36) || c == '_') {
+				return Err(DatabaseError::MalformedStructure(format!("Route parameter must be a whole path segment in: {}", resource_name)));
+			}
+			if parameters.iter().any(|parameter| parameter == name) {
+				return Err(DatabaseError::MalformedStructure(format!("Route parameter {} is repeated in: {}", name, resource_name)));
+			}
+			parameters.push(name.to_string());
+		}
+
+		Ok(parameters)
+	}
+
+	/// Checks a resource name that is a route pattern. Every path matching it gets its own
+	/// instance, so it must be a FORM resource.
+	fn check_route_pattern(resource_name: &str, resource_type: ResourceIO) -> DBResult<()> {
+		if !try!(route_parameters(resource_name)).is_empty() && resource_type != ResourceIO::FORM {
+			return Err(DatabaseError::MalformedStructure(format!("Route pattern is not FORM IO type: {}", resource_name)));
+		}
+
+		Ok(())
+	}
+
+	/// Matches a path against a route pattern segment by segment. Returns the values captured by
+	/// the parameters, in order, or `None` if the path does not match.
+	fn match_route_pattern(pattern: &str, path: &str) -> Option<Vec<String>> {
+		let pattern_segments: Vec<&str> = pattern.split('/').collect();
+		let path_segments: Vec<&str> = path.split('/').collect();
+		if pattern_segments.len() != path_segments.len() {
+			return None;
+		}
+
+		let mut values: Vec<String> = Vec::new();
+		for (pattern_segment, path_segment) in pattern_segments.iter().zip(path_segments.iter()) {
+			if pattern_segment.starts_with('{') {
+				// a parameter captures any non-empty segment
+				if path_segment.is_empty() {
+					return None;
+				}
+				values.push(path_segment.to_string());
+			} else if pattern_segment != path_segment {
+				return None;
+			}
+		}
+
+		Some(values)
+	}
+
+	/// Defines the internal workings of the database. This includes filesystems layout,
+	/// file I/O, and database design.
+	mod internals {
+		use db;
+		use std::path::{Path, PathBuf};
+		use std::borrow::Cow;
+		use std::collections::{HashMap, HashSet};
+		use std::fs;
+		use std::fs::{File, OpenOptions};
+		use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+		use std::sync::Arc;
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		use std::cmp;
+		use bincode::{encode, decode_from, encode_into, SizeLimit};
+		use fs2::{FileExt, lock_contended_error};
+		use rustc_serialize::{Encodable, Decodable};
+		use sha2::{Digest, Sha256};
+
+		// file names inside the database directory
+		static RESOURCES_FILE: &'static str = "resources.db";
+		static COMPONENTS_FILE: &'static str = "components.db";
+		static INSTANCES_FILE: &'static str = "instances.db";
+		static INDEXES_FILE: &'static str = "indexes.db";
+		static LOG_FILE: &'static str = "wal.db";
+		static LOCK_FILE: &'static str = "lock";
+		static VERSION_FILE: &'static str = "version";
+		static BLOBS_DIRECTORY: &'static str = "blobs";
+
+		// increased whenever the layout of the database files changes
+		static FORMAT_VERSION: u32 = 1;
+
+		// numbers the temporary files of blobs being written
+		static NEXT_UPLOAD: AtomicUsize = AtomicUsize::new(0);
+
+		// structs to serialize to file
+
+		#[derive(RustcEncodable, RustcDecodable, Clone, PartialEq, Debug)]
+		pub struct Resources {
+			pub resources: HashMap<String, (usize, db::ResourceIO)>, // resource name : (resource id, static flag)
+			pub resource_instances: HashMap<usize, HashSet<usize>>, // resource id : [instance id]
+			pub models: HashMap<usize, HashMap<usize, db::DataIO>>, // resource id : [component id : data io]
+			pub route_instances: HashMap<usize, HashMap<Vec<String>, usize>>, // resource id : [route parameter values : instance id]
+			pub parents: HashMap<usize, Option<usize>>, // resource id : declared parent resource id, None for a declared root
+			pub next_resource_id: usize, // keeps track of resource ids
+		}
+
+		impl Resources {
+			fn new() -> Resources {
+				Resources {
+					resources: HashMap::new(),
+					resource_instances: HashMap::new(),
+					models: HashMap::new(),
+					route_instances: HashMap::new(),
+					parents: HashMap::new(),
+					next_resource_id: 1,
+				}
+			}
+
+			pub fn load(directory: &Path) -> db::DBResult<Resources> {
+				match load_from_file::<Resources>(&directory.join(RESOURCES_FILE)) {
+					Ok(r) => Ok(r),
+					Err(error) => Err(error)
+				}
+			}
+
+			pub fn save(&self, directory: &Path) -> db::DBResult<()> {
+				save_to_file::<Resources>(&directory.join(RESOURCES_FILE), &self)
+			}
+		}
+
+		#[derive(RustcEncodable, RustcDecodable, Clone, PartialEq, Debug)]
+		pub struct Components {
+			pub components: HashMap<String, usize>,
+			pub component_names: HashMap<usize, String>,
+			pub component_data_types: HashMap<usize, db::DataType>,
+			pub component_indexes: HashMap<usize, db::IndexType>, // component id : index type
+			pub next_component_id: usize
+		}
+
+		impl Components {
+			fn new() -> Components {
+				Components {
+					components: HashMap::new(),
+					component_names: HashMap::new(),
+					component_data_types: HashMap::new(),
+					component_indexes: HashMap::new(),
+					next_component_id: 0
+				}
+			}
+
+			pub fn load(directory: &Path) -> db::DBResult<Components> {
+				match load_from_file::<Components>(&directory.join(COMPONENTS_FILE)) {
+					Ok(r) => Ok(r),
+					Err(error) => Err(error)
+				}
+			}
+
+			pub fn save(&self, directory: &Path) -> db::DBResult<()> {
+				save_to_file::<Components>(&directory.join(COMPONENTS_FILE), &self)
+			}
+		}
+
+		#[derive(RustcEncodable, RustcDecodable, Clone, PartialEq, Debug)]
+		pub struct Instances {
+			pub instances: HashMap<usize, Arc<HashMap<usize, db::Data>>>, // component id : [instance id : Data], shared until changed
+			pub next_instance_id: usize
+		}
+
+		impl Instances {
+			fn new() -> Instances {
+				Instances {
+					instances: HashMap::new(),
+					next_instance_id: 0
+				}
+			}
+
+			pub fn load(directory: &Path) -> db::DBResult<Instances> {
+				match load_from_file::<Instances>(&directory.join(INSTANCES_FILE)) {
+					Ok(r) => Ok(r),
+					Err(error) => Err(error)
+				}
+			}
+
+			pub fn save(&self, directory: &Path) -> db::DBResult<()> {
+				save_to_file::<Instances>(&directory.join(INSTANCES_FILE), &self)
+			}
+		}
+
+		/// The secondary indexes of components. They only hold what can be derived from the
+		/// instances, so they can always be built again from them.
+		#[derive(RustcEncodable, RustcDecodable, Clone, PartialEq, Debug)]
+		pub struct Indexes {
+			pub hash_indexes: HashMap<usize, Arc<HashMap<Vec<u8>, HashSet<usize>>>>, // component id : [encoded value : instance ids], shared until changed
+			pub ordered_indexes: HashMap<usize, Arc<Vec<(db::Data, usize)>>>, // component id : [(value, instance id)], sorted, shared until changed
+		}
+
+		impl Indexes {
+			fn new() -> Indexes {
+				Indexes {
+					hash_indexes: HashMap::new(),
+					ordered_indexes: HashMap::new()
+				}
+			}
+
+			/// Builds every index the components declare from the data of the instances.
+			pub fn build(components: &Components, instances: &Instances) -> Indexes {
+				let mut indexes = Indexes::new();
+				for (component_id, index_type) in components.component_indexes.iter() {
+					indexes.reset(*component_id, Some(*index_type));
+					if let Some(component_instances) = instances.instances.get(component_id) {
+						for (instance_id, data) in component_instances.iter() {
+							indexes.insert(*component_id, *instance_id, data);
+						}
+					}
+				}
+
+				indexes
+			}
+
+			pub fn load(directory: &Path) -> db::DBResult<Indexes> {
+				load_from_file::<Indexes>(&directory.join(INDEXES_FILE))
+			}
+
+			pub fn save(&self, directory: &Path) -> db::DBResult<()> {
+				save_to_file::<Indexes>(&directory.join(INDEXES_FILE), &self)
+			}
+
+			/// Empties the index of a component, or drops it if it no longer has an index type.
+			pub fn reset(&mut self, component_id: usize, index_type: Option<db::IndexType>) {
+				self.hash_indexes.remove(&component_id);
+				self.ordered_indexes.remove(&component_id);
+				match index_type {
+					Some(db::IndexType::HASH) => { self.hash_indexes.insert(component_id, Arc::new(HashMap::new())); },
+					Some(db::IndexType::ORDERED) => { self.ordered_indexes.insert(component_id, Arc::new(Vec::new())); },
+					None => ()
+				};
+			}
+
+			/// Adds the data of an instance to the index of its component, if it has one. The
+			/// elements of a list or set are indexed one by one.
+			pub fn insert(&mut self, component_id: usize, instance_id: usize, data: &db::Data) {
+				if let Some(index) = self.hash_indexes.get_mut(&component_id) {
+					let index = Arc::make_mut(index);
+					for key in index_keys(data) {
+						index.entry(encode_key(&key)).or_insert_with(HashSet::new).insert(instance_id);
+					}
+				}
+				if let Some(index) = self.ordered_indexes.get_mut(&component_id) {
+					let index = Arc::make_mut(index);
+					for key in index_keys(data) {
+						let entry = (key, instance_id);
+						if let Err(position) = index.binary_search_by(|other| compare_entries(other, &entry)) {
+							index.insert(position, entry);
+						}
+					}
+				}
+			}
+
+			/// Removes the data of an instance from the index of its component, if it has one.
+			pub fn remove(&mut self, component_id: usize, instance_id: usize, data: &db::Data) {
+				if let Some(index) = self.hash_indexes.get_mut(&component_id) {
+					let index = Arc::make_mut(index);
+					for key in index_keys(data) {
+						let key = encode_key(&key);
+						let empty = match index.get_mut(&key) {
+							Some(instance_ids) => {
+								instance_ids.remove(&instance_id);
+								instance_ids.is_empty()
+							},
+							None => false
+						};
+						if empty {
+							index.remove(&key);
+						}
+					}
+				}
+				if let Some(index) = self.ordered_indexes.get_mut(&component_id) {
+					let index = Arc::make_mut(index);
+					for key in index_keys(data) {
+						let entry = (key, instance_id);
+						if let Ok(position) = index.binary_search_by(|other| compare_entries(other, &entry)) {
+							index.remove(position);
+						}
+					}
+				}
+			}
+
+			/// Returns the instances holding a value for a component, or `None` if the component
+			/// has no index.
+			pub fn lookup(&self, component_id: usize, value: &db::Data) -> Option<Vec<usize>> {
+				if let Some(index) = self.hash_indexes.get(&component_id) {
+					return Some(index.get(&encode_key(&db::index_value(value))).map_or_else(Vec::new, |instance_ids| instance_ids.iter().cloned().collect()));
+				}
+
+				self.range(component_id, Some(value), Some(value))
+			}
+
+			/// Returns the instances holding a value for a component from `from` up to and
+			/// including `to`, in the order of their values, or `None` if the component has no
+			/// ordered index.
+			pub fn range(&self, component_id: usize, from: Option<&db::Data>, to: Option<&db::Data>) -> Option<Vec<usize>> {
+				let index = match self.ordered_indexes.get(&component_id) {
+					Some(index) => index,
+					None => return None
+				};
+
+				let from = from.map(db::index_value);
+				let to = to.map(db::index_value);
+
+				// entries are sorted by value, so the range starts at the first entry not below from
+				let start = match from {
+					Some(ref from) => {
+						match index.binary_search_by(|entry| match compare_data(&entry.0, from) { cmp::Ordering::Equal => cmp::Ordering::Greater, ordering => ordering }) {
+							Ok(position) | Err(position) => position
+						}
+					},
+					None => 0
+				};
+
+				// an instance holding several elements in the range is only listed once
+				let mut seen: HashSet<usize> = HashSet::new();
+				let mut instance_ids: Vec<usize> = Vec::new();
+				for &(ref value, instance_id) in index[start..].iter() {
+					if to.as_ref().map_or(false, |to| compare_data(value, to) == cmp::Ordering::Greater) {
+						break;
+					}
+					if seen.insert(instance_id) {
+						instance_ids.push(instance_id);
+					}
+				}
+
+				Some(instance_ids)
+			}
+		}
+
+		/// Returns the values data is indexed by: the elements of a list or set, nothing for null
+		/// and the data itself for anything else.
+		fn index_keys(data: &db::Data) -> Vec<db::Data> {
+			match data {
+				&db::Data::LIST(ref elements) | &db::Data::SET(ref elements) => elements.iter().map(db::index_value).collect(),
+				&db::Data::NULL => Vec::new(),
+				data => vec![db::index_value(data)]
+			}
+		}
+
+		fn encode_key(data: &db::Data) -> Vec<u8> {
+			// encoding into memory without a size limit cannot fail
+			encode(data, SizeLimit::Infinite).unwrap()
+		}
+
+		fn compare_data(a: &db::Data, b: &db::Data) -> cmp::Ordering {
+			a.partial_cmp(b).unwrap_or(cmp::Ordering::Equal)
+		}
+
+		fn compare_entries(a: &(db::Data, usize), b: &(db::Data, usize)) -> cmp::Ordering {
+			match compare_data(&a.0, &b.0) {
+				cmp::Ordering::Equal => a.1.cmp(&b.1),
+				ordering => ordering
+			}
+		}
+
+		/// An operation in the write-ahead log. It holds the new state of every file the operation
+		/// changes, so replaying it only means saving those files again. The state is borrowed when
+		/// writing an entry and owned when reading one back.
+		#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug)]
+		pub struct LogEntry<'a> {
+			pub operation: String,
+			pub resources: Option<Cow<'a, Resources>>,
+			pub components: Option<Cow<'a, Components>>,
+			pub instances: Option<Cow<'a, Instances>>,
+			pub indexes: Option<Cow<'a, Indexes>>
+		}
+
+		impl<'a> LogEntry<'a> {
+			pub fn new(operation: String) -> LogEntry<'a> {
+				LogEntry {
+					operation: operation,
+					resources: None,
+					components: None,
+					instances: None,
+					indexes: None
+				}
+			}
+
+			/// Writes the entry to the log before touching any state file, then checkpoints the
+			/// files and clears the log. If this is interrupted, the entry is replayed on open.
+			pub fn commit(&self, directory: &Path) -> db::DBResult<()> {
+				try!(append_to_log(directory, &self));
+				try!(self.checkpoint(directory));
+				try!(clear_log(directory));
+
+				Ok(())
+			}
+
+			/// Saves every state file held by the entry.
+			fn checkpoint(&self, directory: &Path) -> db::DBResult<()> {
+				if let Some(ref resources) = self.resources {
+					try!(resources.save(directory));
+				}
+				if let Some(ref components) = self.components {
+					try!(components.save(directory));
+				}
+				if let Some(ref instances) = self.instances {
+					try!(instances.save(directory));
+				}
+				if let Some(ref indexes) = self.indexes {
+					try!(indexes.save(directory));
+				}
+
+				Ok(())
+			}
+		}
+
+		pub fn start_from_scratch(directory: &Path) -> db::DBResult<()> {
+			// create the directory, the files are created when saving
+			try!(create_directory(directory));
+
+			// forget anything logged for a previous database
+			try!(clear_log(directory));
+
+			// mark the files as written in the current format
+			try!(save_to_file::<u32>(&directory.join(VERSION_FILE), &FORMAT_VERSION));
+
+			// create default data
+			let mut entry = LogEntry::new("reset".to_string());
+			entry.resources = Some(Cow::Owned(Resources::new()));
+			entry.components = Some(Cow::Owned(Components::new()));
+			entry.instances = Some(Cow::Owned(Instances::new()));
+			entry.indexes = Some(Cow::Owned(Indexes::new()));
+			try!(entry.commit(directory));
+
+			Ok(())
+		}
+
+		/// Returns whether a directory holds any database file.
+		pub fn database_exists(directory: &Path) -> bool {
+			[RESOURCES_FILE, COMPONENTS_FILE, INSTANCES_FILE, LOG_FILE, VERSION_FILE].iter().any(|filename| directory.join(filename).exists())
+		}
+
+		/// Checks that the files in a directory were written in the format this release reads.
+		/// Databases from before the format was versioned have no version file at all.
+		pub fn check_version(directory: &Path) -> db::DBResult<()> {
+			let path = directory.join(VERSION_FILE);
+			if !path.exists() {
+				if database_exists(directory) {
+					return Err(db::DatabaseError::IncompatibleVersion(format!("Database was written in an older format and has to be recreated: {}", directory.display())));
+				}
+				// nothing there, opening reports the missing files
+				return Ok(());
+			}
+
+			let file = try!(open_file_for_reading(&path));
+			let mut reader = BufReader::new(&file);
+			match decode_from::<_, u32>(&mut reader, SizeLimit::Bounded(4)) {
+				Ok(version) if version == FORMAT_VERSION => Ok(()),
+				Ok(version) => Err(db::DatabaseError::IncompatibleVersion(format!("Database was written in format version {}, expected version {}: {}", version, FORMAT_VERSION, directory.display()))),
+				Err(error) => Err(db::DatabaseError::IncompatibleVersion(format!("Could not read the format version, {}: {}", path.display(), error)))
+			}
+		}
+
+		pub fn create_directory(path: &Path) -> db::DBResult<()> {
+			match fs::create_dir_all(path) {
+				Ok(_) => Ok(()),
+				Err(error) => {
+					match error.kind() {
+						ErrorKind::AlreadyExists => Ok(()),
+						_ => Err(db::DatabaseError::CouldNotCreateDirectory(format!("Could not create directory, {}: {}", path.display(), error)))
+					}
+				}
+			}
+		}
+
+		/// Takes the advisory lock on a database directory, exclusively for writers or shared for
+		/// readers. The lock is held until the returned file is closed.
+		pub fn lock_directory(directory: &Path, exclusive: bool) -> db::DBResult<File> {
+			let path = directory.join(LOCK_FILE);
+			let file = match OpenOptions::new().read(true).write(true).create(true).open(&path) {
+				Ok(file) => file,
+				Err(error) => {
+					match error.kind() {
+						ErrorKind::NotFound => return Err(db::DatabaseError::FileNotFound(format!("Could not open file, {}: {}", path.display(), error))),
+						_ => return Err(db::DatabaseError::CouldNotOpenFile(format!("Could not open file, {}: {}", path.display(), error)))
+					}
+				}
+			};
+
+			let locked = if exclusive { FileExt::try_lock_exclusive(&file) } else { FileExt::try_lock_shared(&file) };
+			match locked {
+				Ok(_) => Ok(file),
+				Err(error) => {
+					if error.raw_os_error() == lock_contended_error().raw_os_error() {
+						Err(db::DatabaseError::DatabaseLocked(format!("Database is locked by another process: {}", directory.display())))
+					} else {
+						Err(db::DatabaseError::CouldNotOpenFile(format!("Could not lock file, {}: {}", path.display(), error)))
+					}
+				}
+			}
+		}
+
+		fn create_file(path: &Path) -> db::DBResult<File> {
+			match File::create(path) {
+				Ok(file) => Ok(file),
+				Err(error)  => Err(db::DatabaseError::CouldNotCreateFile(format!("Could not create file, {}: {}", path.display(), error)))
+			}
+		}
+
+		fn open_file_for_reading(path: &Path) -> db::DBResult<File> {
+			match OpenOptions::new().read(true).write(false).open(path) {
+				Ok(file) => Ok(file),
+				Err(error)  => return Err(db::DatabaseError::FileNotFound(format!("Could not open file, {}: {}", path.display(), error)))
+			}
+		}
+
+		fn load_from_file<T>(path: &Path) -> db::DBResult<T> where T: Decodable {
+			let file = try!(open_file_for_reading(path));
+
+			// TODO: check if empty, if so then do something useful
+			let mut reader = BufReader::new(&file);
+
+			match decode_from(&mut reader, SizeLimit::Infinite) {
+				Ok(value) => Ok(value),
+				Err(error) => {
+					return Err(db::DatabaseError::FileNotFound(format!("Could not decode file, {}: {}", path.display(), error)));
+				}
+			}
+		}
+
+		/// Saves a value by writing it to a temporary file next to the target, syncing it to disk
+		/// and renaming it over the target. A crash at any point leaves either the old or the new
+		/// file in place, never a partially written one.
+		fn save_to_file<T>(path: &Path, value: &T) -> db::DBResult<()> where T: Encodable {
+			let temporary = temporary_path(path);
+
+			{ // scope the file so it is closed before the rename
+				let file = try!(create_file(&temporary));
+				let mut writer = BufWriter::new(&file);
+
+				// save definitions to the temporary file
+				match encode_into(&value, &mut writer, SizeLimit::Infinite) {
+					Err(error) => { return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to save to file: {}, Reason: {}", temporary.display(), error))); }
+					_ => ()
+				};
+				match writer.flush().and_then(|_| file.sync_all()) {
+					Err(error) => { return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to sync file: {}, Reason: {}", temporary.display(), error))); }
+					_ => ()
+				};
+			}
+
+			// atomically replace the old file
+			match fs::rename(&temporary, path) {
+				Err(error) => { return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to replace file: {}, Reason: {}", path.display(), error))); }
+				_ => ()
+			};
+			if let Some(directory) = path.parent() {
+				sync_directory(directory);
+			}
+
+			Ok(())
+		}
+
+		/// Discards temporary files left behind by saves that did not finish and replays any
+		/// operation still in the write-ahead log. The files the temporary files were meant to
+		/// replace are still intact, since a temporary file is only renamed into place once it
+		/// is completely written.
+		pub fn recover(directory: &Path) -> db::DBResult<()> {
+			for filename in [RESOURCES_FILE, COMPONENTS_FILE, INSTANCES_FILE, INDEXES_FILE].iter() {
+				try!(remove_file_if_exists(&temporary_path(&directory.join(filename))));
+			}
+			for path in try!(list_blob_files(directory)) {
+				if path.extension().map_or(false, |extension| extension == "tmp") {
+					try!(remove_file_if_exists(&path));
+				}
+			}
+
+			try!(replay_log(directory));
+
+			Ok(())
+		}
+
+		fn append_to_log(directory: &Path, entry: &LogEntry) -> db::DBResult<()> {
+			let path = directory.join(LOG_FILE);
+			let file = match OpenOptions::new().append(true).create(true).open(&path) {
+				Ok(file) => file,
+				Err(error) => return Err(db::DatabaseError::CouldNotOpenFile(format!("Could not open file, {}: {}", path.display(), error)))
+			};
+			let mut writer = BufWriter::new(&file);
+
+			match encode_into(entry, &mut writer, SizeLimit::Infinite) {
+				Err(error) => { return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to log operation: {}, Reason: {}", entry.operation, error))); }
+				_ => ()
+			};
+			match writer.flush().and_then(|_| file.sync_all()) {
+				Err(error) => { return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to sync file: {}, Reason: {}", path.display(), error))); }
+				_ => ()
+			};
+
+			Ok(())
+		}
+
+		fn clear_log(directory: &Path) -> db::DBResult<()> {
+			try!(remove_file_if_exists(&directory.join(LOG_FILE)));
+			sync_directory(directory);
+
+			Ok(())
+		}
+
+		/// Checkpoints every complete entry in the log, in order, then clears the log.
+		fn replay_log(directory: &Path) -> db::DBResult<()> {
+			for entry in try!(read_log(directory)) {
+				try!(entry.checkpoint(directory));
+			}
+
+			try!(clear_log(directory));
+
+			Ok(())
+		}
+
+		/// Returns every complete entry in the log, in order. An entry that cannot be decoded was
+		/// cut off while being written, so none of its files were touched and it is discarded
+		/// along with anything after it.
+		pub fn read_log(directory: &Path) -> db::DBResult<Vec<LogEntry<'static>>> {
+			let path = directory.join(LOG_FILE);
+			let mut entries = Vec::new();
+			let mut bytes: Vec<u8> = Vec::new();
+			match File::open(&path) {
+				Ok(mut file) => {
+					match file.read_to_end(&mut bytes) {
+						Err(error) => return Err(db::DatabaseError::FailedToLoadFile(format!("Could not read file, {}: {}", path.display(), error))),
+						_ => ()
+					};
+				},
+				Err(error) => {
+					match error.kind() {
+						ErrorKind::NotFound => return Ok(entries),
+						_ => return Err(db::DatabaseError::CouldNotOpenFile(format!("Could not open file, {}: {}", path.display(), error)))
+					}
+				}
+			};
+
+			let mut reader: &[u8] = &bytes;
+			while reader.len() > 0 {
+				// bound the decoder by what is left, so a cut off length cannot over allocate
+				let limit = SizeLimit::Bounded(reader.len() as u64);
+				match decode_from(&mut reader, limit) {
+					Ok(entry) => entries.push(entry),
+					Err(_) => break
+				};
+			}
+
+			Ok(entries)
+		}
+
+		/// Streams data into the blobs directory, hashing it on the way, and returns its hash and
+		/// length. The data is written to a temporary file that is renamed to the hash once it is
+		/// synced, so a blob file is always complete. Data that is already stored is not stored
+		/// again.
+		pub fn write_blob<R: Read>(directory: &Path, reader: &mut R) -> db::DBResult<(String, u64)> {
+			let blobs = directory.join(BLOBS_DIRECTORY);
+			try!(create_directory(&blobs));
+			let temporary = blobs.join(format!("upload-{}.tmp", NEXT_UPLOAD.fetch_add(1, Ordering::SeqCst)));
+
+			let mut hasher = Sha256::new();
+			let length = match stream_to_file(reader, &temporary, &mut hasher) {
+				Ok(length) => length,
+				Err(error) => {
+					remove_file_if_exists(&temporary);
+					return Err(error);
+				}
+			};
+
+			let hash = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<String>>().concat();
+			let path = blobs.join(&hash);
+			if path.exists() {
+				// identical data is already stored
+				try!(remove_file_if_exists(&temporary));
+			} else {
+				match fs::rename(&temporary, &path) {
+					Err(error) => { return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to replace file: {}, Reason: {}", path.display(), error))); }
+					_ => ()
+				};
+				sync_directory(&blobs);
+			}
+
+			Ok((hash, length))
+		}
+
+		/// Copies data into a new, synced file while hashing it. Returns the length of the data.
+		fn stream_to_file<R: Read>(reader: &mut R, path: &Path, hasher: &mut Sha256) -> db::DBResult<u64> {
+			let file = try!(create_file(path));
+			let mut writer = BufWriter::new(&file);
+			let mut buffer = [0u8; 8192];
+			let mut length: u64 = 0;
+			loop {
+				let read = match reader.read(&mut buffer) {
+					Ok(0) => break,
+					Ok(read) => read,
+					Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+					Err(error) => return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to read data for: {}, Reason: {}", path.display(), error)))
+				};
+				hasher.update(&buffer[..read]);
+				length = length + read as u64;
+				match writer.write_all(&buffer[..read]) {
+					Err(error) => { return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to save to file: {}, Reason: {}", path.display(), error))); }
+					_ => ()
+				};
+			}
+			match writer.flush().and_then(|_| file.sync_all()) {
+				Err(error) => { return Err(db::DatabaseError::FailedToSaveFile(format!("Failed to sync file: {}, Reason: {}", path.display(), error))); }
+				_ => ()
+			};
+
+			Ok(length)
+		}
+
+		pub fn open_blob(directory: &Path, hash: &str) -> db::DBResult<File> {
+			// a hash never leaves the blobs directory
+			if !is_blob_hash(hash) {
+				return Err(db::DatabaseError::MalformedData(format!("Blob hash is not SHA-256: {}", hash)));
+			}
+
+			open_file_for_reading(&directory.join(BLOBS_DIRECTORY).join(hash))
+		}
+
+		/// Returns whether a blob file is stored for a hash.
+		pub fn has_blob(directory: &Path, hash: &str) -> bool {
+			is_blob_hash(hash) && directory.join(BLOBS_DIRECTORY).join(hash).is_file()
+		}
+
+		fn is_blob_hash(hash: &str) -> bool {
+			hash.len() == 64 && hash.chars().all(|c| c.is_digit(16))
+		}
+
+		/// Removes every blob file whose hash is not in use and returns how many were removed.
+		pub fn remove_unused_blobs(directory: &Path, used: &HashSet<String>) -> db::DBResult<usize> {
+			let mut removed = 0;
+			for path in try!(list_blob_files(directory)) {
+				let unused = match path.file_name().and_then(|name| name.to_str()) {
+					Some(name) => !name.ends_with(".tmp") && !used.contains(name),
+					None => false
+				};
+				if unused {
+					try!(remove_file_if_exists(&path));
+					removed = removed + 1;
+				}
+			}
+			if removed > 0 {
+				sync_directory(&directory.join(BLOBS_DIRECTORY));
+			}
+
+			Ok(removed)
+		}
+
+		fn list_blob_files(directory: &Path) -> db::DBResult<Vec<PathBuf>> {
+			let blobs = directory.join(BLOBS_DIRECTORY);
+			let entries = match fs::read_dir(&blobs) {
+				Ok(entries) => entries,
+				Err(error) => {
+					match error.kind() {
+						ErrorKind::NotFound => return Ok(Vec::new()),
+						_ => return Err(db::DatabaseError::CouldNotOpenFile(format!("Could not open directory, {}: {}", blobs.display(), error)))
+					}
+				}
+			};
+
+			let mut paths: Vec<PathBuf> = Vec::new();
+			for entry in entries {
+				match entry {
+					Ok(entry) => paths.push(entry.path()),
+					Err(error) => return Err(db::DatabaseError::CouldNotOpenFile(format!("Could not read directory, {}: {}", blobs.display(), error)))
+				};
+			}
+
+			Ok(paths)
+		}
+
+		fn temporary_path(path: &Path) -> PathBuf {
+			let mut temporary = path.as_os_str().to_os_string();
+			temporary.push(".tmp");
+			PathBuf::from(temporary)
+		}
+
+		fn remove_file_if_exists(path: &Path) -> db::DBResult<()> {
+			match fs::remove_file(path) {
+				Ok(_) => Ok(()),
+				Err(error) => {
+					match error.kind() {
+						ErrorKind::NotFound => Ok(()),
+						_ => Err(db::DatabaseError::CouldNotRemoveFile(format!("Could not remove file, {}: {}", path.display(), error)))
+					}
+				}
+			}
+		}
+
+		/// Makes a rename inside the directory durable. Not every platform can open a directory,
+		/// so this is best effort.
+		fn sync_directory(path: &Path) {
+			if let Ok(directory) = File::open(path) {
+				directory.sync_all();
+			}
+		}
+
+		#[test]
+		fn test_replay_log() {
+			let directory = ::test_directory("replay_log");
+			match start_from_scratch(&directory) {
+				Err(error) => panic!("{:?}", error),
+				_ => ()
+			};
+
+			// log an operation that changes two files, then crash before checkpointing it
+			let mut resources = Resources::new();
+			resources.resources.insert("/login/".to_string(), (1, db::ResourceIO::STATIC));
+			let mut instances = Instances::new();
+			instances.next_instance_id = 5;
+			let mut entry = LogEntry::new("crashed".to_string());
+			entry.resources = Some(Cow::Owned(resources));
+			entry.instances = Some(Cow::Owned(instances));
+			match append_to_log(&directory, &entry) {
+				Err(error) => panic!("{:?}", error),
+				_ => ()
+			};
+
+			// a second operation was cut off while being logged
+			{
+				let mut file = OpenOptions::new().append(true).open(directory.join(LOG_FILE)).unwrap();
+				file.write_all(&[0, 0, 0]).unwrap();
+			}
+
+			match recover(&directory) {
+				Err(error) => panic!("{:?}", error),
+				_ => ()
+			};
+			assert!(!directory.join(LOG_FILE).exists());
+			assert_eq!(entry.resources.unwrap().into_owned(), Resources::load(&directory).unwrap());
+			assert_eq!(entry.instances.unwrap().into_owned(), Instances::load(&directory).unwrap());
+			assert_eq!(Components::new(), Components::load(&directory).unwrap());
+		}
+
+		#[test]
+		fn test_blob_hash() {
+			let directory = ::test_directory("blob_hash");
+			match start_from_scratch(&directory) {
+				Err(error) => panic!("{:?}", error),
+				_ => ()
+			};
+
+			// blobs are named by the SHA-256 of their content
+			match write_blob(&directory, &mut &b"abc"[..]) {
+				Ok((hash, length)) => {
+					assert_eq!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad", hash);
+					assert_eq!(3, length);
+				},
+				Err(error) => panic!("{:?}", error)
+			};
+			match write_blob(&directory, &mut &b""[..]) {
+				Ok((hash, _)) => assert_eq!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", hash),
+				Err(error) => panic!("{:?}", error)
+			};
+		}
+
+		/*
+		// TODO: return an error instead of panicing
+		fn encode<T>(value: &T) -> Vec<u8> where T: Encodable {
+			encode(&value, SizeLimit::Infinite).unwrap_or(panic!("Could not encode Value<T>."))
+		}
+
+		// TODO: return an error instead of panicing
+		fn decode<T>(encoded: &Vec<u8>) -> T where T: Decodable {
+			bincode::decode(&encoded[..]).unwrap_or(panic!("Could not decode to Value<T>."))
+		}
+		*/
+	}
+}
+
+/*
+#[test]
+fn test_setup() {
+	match db::reset() {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+}
+*/
+
+
+/// A static resource means that there is only one instance (copy) of that resource. This means
+/// that it can only hold static components.
+
+/// A static component means that there is only one instance of it.
+/// Types of IO: DBInput, DBReadOnly, DBBoth, and Static
+/// DB types reference a specific data slot in the database where information is stored. A static 
+/// type is a field that is only there for data processing, such as username and password on
+/// a login page. DB types are only allowed on non-static resources.
+
+
+fn get_model(database: &db::Database, resource_name: &str) -> HashMap<String, db::ComponentInstance> {
+	let model: HashMap<String, db::ComponentInstance> = {
+		let is_static = match database.is_static_resource(resource_name) {
+			Err(error) => panic!("{:?}", error),
+			Ok(b) => b
+		};
+		if is_static {
+			// static resource, so just get the associated model
+			match database.load_static_model(resource_name) {
+				Err(error) => panic!("{:?}", error),
+				Ok(model) => model
+			}
+		} else {
+			// use instance id, if applicable
+			match database.load_model(resource_name, 1) {
+				Err(error) => panic!("{:?}", error),
+				Ok(model) => model
+			}
+		}
+	};
+
+	model
+}
+
+/// Returns a fresh directory for a test database, so tests do not share files.
+fn test_directory(name: &str) -> PathBuf {
+	let path = env::temp_dir().join(format!("codb_test_{}", name));
+	fs::remove_dir_all(&path);
+	path
+}
+
+fn pass_filter(tainted: String) -> String {
+	tainted
+}
+
+fn empty_filter(_: String) -> String {
+	String::new()
+}
+
+#[test]
+fn test_database() {
+	// test admin functions
+
+	let database = match db::Database::create(test_directory("database")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+
+	match database.add_resource("/login/", db::ResourceIO::STATIC, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	let blog_post_instance_id = match database.next_instance_id() {
+		Err(error) => panic!("{:?}", error),
+		Ok(id) => id
+	};
+
+	match database.add_resource("/blog/username/my_first_post/", db::ResourceIO::FORM, Some(blog_post_instance_id)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.add_component("blogpost", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.add_component("username", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.add_component("password", db::DataType::PASSWORD) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.add_component_to_model("/login/", "username", db::DataIO::STATIC) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.add_component_to_model("/login/", "password", db::DataIO::STATIC) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.add_component_to_model("/blog/username/my_first_post/", "blogpost", db::DataIO::DB_READ_ONLY) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.add_component("title", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.add_component_to_model("/blog/username/my_first_post/", "title", db::DataIO::DB_INPUT) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	// test API functions
+
+	let data_type1 = match database.get_component_data_type("username") {
+		Err(error) => panic!("{:?}", error),
+		Ok(data_type) => data_type
+	};
+	assert_eq!(db::DataType::STRING, data_type1);
+
+	let data_type2 = match database.get_component_data_type("password") {
+		Err(error) => panic!("{:?}", error),
+		Ok(data_type) => data_type
+	};
+	assert_eq!(db::DataType::PASSWORD, data_type2);
+
+	let model1 = get_model(&database, "/login/");
+
+	match database.save_model(model1, "/login/", 1) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	let model2 = get_model(&database, "/login/");
+
+	assert!(model2.contains_key("username"));
+
+
+	// test static resource
+	{
+		let is_static = match database.is_static_resource("/login/") {
+			Err(error) => panic!("{:?}", error),
+			Ok(b) => b
+		};
+		assert!(is_static);
+
+		// static resource, so just get the associated model
+		match database.load_static_model("/login/") {
+			Err(error) => panic!("{:?}", error),
+			Ok(model) => model
+		};
+	}
+
+	// test form resource
+	{
+		let is_static = match database.is_static_resource("/blog/username/my_first_post/") {
+			Err(error) => panic!("{:?}", error),
+			Ok(b) => b
+		};
+		assert!(!is_static);
+
+		// get the associated model
+		match database.load_model("/blog/username/my_first_post/", blog_post_instance_id) {
+			Err(error) => panic!("{:?}", error),
+			Ok(model) => model
+		};
+	}
+
+
+	// test input data
+	{
+		let resource_name = "/blog/username/my_first_post/";
+
+		match database.update(resource_name, "title", blog_post_instance_id, db::Tainted::new("Hello".to_string(), pass_filter)) {
+			Err(db::DatabaseError::InstanceNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+
+		match database.insert(resource_name, "title", blog_post_instance_id, db::Tainted::new("Hello".to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+
+		match database.insert(resource_name, "title", blog_post_instance_id, db::Tainted::new("Hello".to_string(), pass_filter)) {
+			Err(db::DatabaseError::DataAlreadyDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+
+		match database.update(resource_name, "title", blog_post_instance_id, db::Tainted::new("Hello, world".to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+
+		match database.select(resource_name, "title", blog_post_instance_id) {
+			Err(error) => panic!("{:?}", error),
+			Ok(data) => assert_eq!(db::Data::STRING("Hello, world".to_string()), data)
+		};
+
+		match database.merge(resource_name, "title", blog_post_instance_id, db::Tainted::new("<b>".to_string(), empty_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+
+		match database.select(resource_name, "title", blog_post_instance_id) {
+			Err(error) => panic!("{:?}", error),
+			Ok(data) => assert_eq!(db::Data::STRING("".to_string()), data)
+		};
+
+		match database.select(resource_name, "title", blog_post_instance_id + 1) {
+			Err(db::DatabaseError::InstanceNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+
+		match database.select("/blog/", "title", blog_post_instance_id) {
+			Err(db::DatabaseError::ResourceNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+
+		match database.select(resource_name, "username", blog_post_instance_id) {
+			Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+	}
+
+
+	// test read only data
+	{
+		match database.merge("/blog/username/my_first_post/", "blogpost", blog_post_instance_id, db::Tainted::new("Post".to_string(), pass_filter)) {
+			Err(db::DatabaseError::ComponentNotWritable(_)) => (),
+			other => panic!("{:?}", other)
+		};
+	}
+
+
+	// test input and read only data
+	{
+		let resource_name = "/blog/username/my_first_post/";
+
+		let mut model = match database.load_model(resource_name, blog_post_instance_id) {
+			Err(error) => panic!("{:?}", error),
+			Ok(model) => model
+		};
+		model.get_mut("title").unwrap().data = db::Data::STRING("Saved title".to_string());
+		model.get_mut("blogpost").unwrap().data = db::Data::STRING("Ignored post".to_string());
+
+		match database.save_model(model, resource_name, blog_post_instance_id) {
+			Err(error) => panic!("{:?}", error),
+			Ok(skipped) => assert_eq!(vec!["blogpost".to_string()], skipped)
+		};
+
+		let model = match database.load_model(resource_name, blog_post_instance_id) {
+			Err(error) => panic!("{:?}", error),
+			Ok(model) => model
+		};
+		assert_eq!(db::Data::STRING("Saved title".to_string()), model.get("title").unwrap().data);
+		assert_eq!(db::Data::NULL, model.get("blogpost").unwrap().data);
+
+		match database.save_model(model, "/blog/", blog_post_instance_id) {
+			Err(db::DatabaseError::ResourceNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+
+		let model = database.load_model(resource_name, blog_post_instance_id).unwrap();
+		match database.save_model(model, resource_name, blog_post_instance_id + 1) {
+			Err(db::DatabaseError::InstanceNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+
+		// components from another resource model are rejected
+		let model = database.load_static_model("/login/").unwrap();
+		match database.save_model(model, resource_name, blog_post_instance_id) {
+			Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+	}
+
+	// test static data	
+}
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#[test]
+fn test_separate_databases() {
+	match db::Database::open(test_directory("missing")) {
+		Err(db::DatabaseError::FileNotFound(_)) => (),
+		Err(error) => panic!("{:?}", error),
+		Ok(_) => panic!("Opened a database that does not exist")
+	};
+
+	let first = match db::Database::create(test_directory("first")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	let second = match db::Database::create(test_directory("second")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+
+	match first.add_component("username", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match second.get_component_data_type("username") {
+		Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// reopening finds the data written before
+	let path = first.path().to_path_buf();
+	drop(first);
+	let reopened = match db::Database::open(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	match reopened.get_component_data_type("username") {
+		Err(error) => panic!("{:?}", error),
+		Ok(data_type) => assert_eq!(db::DataType::STRING, data_type)
+	};
+
+	// creating a database never replaces one
+	drop(reopened);
+	match db::Database::create(&path) {
+		Err(db::DatabaseError::AlreadyDefined(_)) => (),
+		Err(error) => panic!("{:?}", error),
+		Ok(_) => panic!("Replaced an existing database")
+	};
+	let reopened = match db::Database::open(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	assert!(reopened.get_component_data_type("username").is_ok());
+
+	// databases written in another format are refused instead of misread
+	drop(reopened);
+	{
+		let mut file = fs::File::create(path.join("version")).unwrap();
+		file.write_all(&bincode::encode(&0u32, bincode::SizeLimit::Infinite).unwrap()).unwrap();
+	}
+	match db::Database::open(&path) {
+		Err(db::DatabaseError::IncompatibleVersion(_)) => (),
+		other => panic!("{:?}", other.map(|_| ()))
+	};
+	fs::remove_file(path.join("version")).unwrap();
+	match db::Database::open_read_only(&path) {
+		Err(db::DatabaseError::IncompatibleVersion(_)) => (),
+		other => panic!("{:?}", other.map(|_| ()))
+	};
+}
+
+#[test]
+fn test_recover_interrupted_save() {
+	let path = test_directory("recover");
+	{
+		let database = match db::Database::create(&path) {
+			Err(error) => panic!("{:?}", error),
+			Ok(database) => database
+		};
+		match database.add_component("username", db::DataType::STRING) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+
+	// simulate a crash in the middle of saving the components
+	{
+		let mut file = fs::File::create(path.join("components.db.tmp")).unwrap();
+		file.write_all(&[1, 2, 3]).unwrap();
+	}
+
+	let database = match db::Database::open(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	assert!(!path.join("components.db.tmp").exists());
+	match database.get_component_data_type("username") {
+		Err(error) => panic!("{:?}", error),
+		Ok(data_type) => assert_eq!(db::DataType::STRING, data_type)
+	};
+}
+
+#[test]
+fn test_failed_commit() {
+	let path = test_directory("failed_commit");
+	let database = match db::Database::create(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+
+	// the resources are written, then the instances cannot be
+	fs::create_dir(path.join("instances.db.tmp")).unwrap();
+	match database.add_resource("/x/", db::ResourceIO::FORM, Some(100)) {
+		Err(db::DatabaseError::FailedToSaveFile(_)) | Err(db::DatabaseError::CouldNotCreateFile(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// no later commit may clear the log of the failed one
+	match database.add_component("username", db::DataType::STRING) {
+		Err(db::DatabaseError::DatabaseFailed(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// reopening replays the whole failed commit
+	drop(database);
+	fs::remove_dir(path.join("instances.db.tmp")).unwrap();
+	let database = match db::Database::open(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	assert!(database.has_instance("/x/", 100).unwrap());
+	assert!(database.next_instance_id().unwrap() > 100);
+	match database.add_component("username", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+}
+
+#[test]
+fn test_transactions() {
+	let database = match db::Database::create(test_directory("transactions")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+
+	// staged changes are only visible once committed
+	{
+		let mut transaction = match database.begin() {
+			Err(error) => panic!("{:?}", error),
+			Ok(transaction) => transaction
+		};
+		let instance_id = match transaction.next_instance_id() {
+			Err(error) => panic!("{:?}", error),
+			Ok(id) => id
+		};
+		match transaction.add_resource("/profile/", db::ResourceIO::FORM, Some(instance_id)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		match transaction.add_component("username", db::DataType::STRING) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		match transaction.add_component_to_model("/profile/", "username", db::DataIO::DB_BOTH) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		match transaction.insert("/profile/", "username", instance_id, db::Tainted::new("alice".to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+
+		match database.get_component_data_type("username") {
+			Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+
+		match transaction.commit() {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+
+		match database.select("/profile/", "username", instance_id) {
+			Err(error) => panic!("{:?}", error),
+			Ok(data) => assert_eq!(db::Data::STRING("alice".to_string()), data)
+		};
+	}
+
+	// a failed operation rolls back everything staged before it
+	{
+		let mut transaction = database.begin().unwrap();
+		match transaction.add_component("password", db::DataType::PASSWORD) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		match transaction.add_component_to_model("/login/", "password", db::DataIO::STATIC) {
+			Err(db::DatabaseError::ResourceNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+		match transaction.add_resource("/login/", db::ResourceIO::STATIC, None) {
+			Err(db::DatabaseError::TransactionRolledBack(_)) => (),
+			other => panic!("{:?}", other)
+		};
+		match transaction.commit() {
+			Err(db::DatabaseError::TransactionRolledBack(_)) => (),
+			other => panic!("{:?}", other)
+		};
+		match database.get_component_data_type("password") {
+			Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+	}
+
+	// an explicit rollback discards staged changes
+	{
+		let mut transaction = database.begin().unwrap();
+		match transaction.add_component("password", db::DataType::PASSWORD) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		transaction.rollback();
+		match database.get_component_data_type("password") {
+			Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+			other => panic!("{:?}", other)
+		};
+	}
+}
+
+#[test]
+fn test_cached_reads() {
+	let path = test_directory("cached_reads");
+	let database = match db::Database::create(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	match database.add_component("username", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	// reads are served from memory
+	fs::remove_file(path.join("components.db")).unwrap();
+	match database.get_component_data_type("username") {
+		Err(error) => panic!("{:?}", error),
+		Ok(data_type) => assert_eq!(db::DataType::STRING, data_type)
+	};
+
+	// writes go through to the files
+	match database.add_component("password", db::DataType::PASSWORD) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	drop(database);
+	let reopened = match db::Database::open(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	match reopened.get_component_data_type("password") {
+		Err(error) => panic!("{:?}", error),
+		Ok(data_type) => assert_eq!(db::DataType::PASSWORD, data_type)
+	};
+}
+
+#[test]
+fn test_shared_between_threads() {
+	fn assert_send_sync<T: Send + Sync>() {}
+	assert_send_sync::<db::Database>();
+
+	let database = match db::Database::create(test_directory("threads")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => Arc::new(database)
+	};
+
+	let mut handles = Vec::new();
+	for thread in 0..8 {
+		let database = database.clone();
+		handles.push(thread::spawn(move || {
+			// every thread adds its own component, none of them may be lost
+			match database.add_component(&format!("component_{}", thread), db::DataType::STRING) {
+				Err(error) => panic!("{:?}", error),
+				_ => ()
+			};
+
+			let mut instance_ids = Vec::new();
+			for _ in 0..10 {
+				match database.next_instance_id() {
+					Err(error) => panic!("{:?}", error),
+					Ok(id) => instance_ids.push(id)
+				};
+			}
+			instance_ids
+		}));
+	}
+
+	let mut instance_ids = HashSet::new();
+	for handle in handles {
+		for id in handle.join().unwrap() {
+			assert!(instance_ids.insert(id), "Instance id handed out twice: {}", id);
+		}
+	}
+	assert_eq!(80, instance_ids.len());
+
+	for thread in 0..8 {
+		match database.get_component_data_type(&format!("component_{}", thread)) {
+			Err(error) => panic!("{:?}", error),
+			Ok(data_type) => assert_eq!(db::DataType::STRING, data_type)
+		};
+	}
+}
+
+#[test]
+fn test_directory_lock() {
+	let path = test_directory("lock");
+	let database = match db::Database::create(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	match database.add_component("username", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	// a writer keeps everyone else out
+	match db::Database::open(&path) {
+		Err(db::DatabaseError::DatabaseLocked(_)) => (),
+		Err(error) => panic!("{:?}", error),
+		Ok(_) => panic!("Opened a locked database")
+	};
+	match db::Database::open_read_only(&path) {
+		Err(db::DatabaseError::DatabaseLocked(_)) => (),
+		Err(error) => panic!("{:?}", error),
+		Ok(_) => panic!("Opened a locked database")
+	};
+	drop(database);
+
+	// readers share the directory, but keep writers out
+	let first = match db::Database::open_read_only(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	let second = match db::Database::open_read_only(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	match db::Database::open(&path) {
+		Err(db::DatabaseError::DatabaseLocked(_)) => (),
+		Err(error) => panic!("{:?}", error),
+		Ok(_) => panic!("Opened a locked database")
+	};
+
+	match first.get_component_data_type("username") {
+		Err(error) => panic!("{:?}", error),
+		Ok(data_type) => assert_eq!(db::DataType::STRING, data_type)
+	};
+	match second.add_component("password", db::DataType::PASSWORD) {
+		Err(db::DatabaseError::ReadOnlyDatabase(_)) => (),
+		other => panic!("{:?}", other)
+	};
+}
+
+#[test]
+fn test_deletes() {
+	let database = match db::Database::create(test_directory("deletes")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+
+	let instance_id = database.next_instance_id().unwrap();
+	for &(resource_name, instance) in [("/post/", Some(instance_id)), ("/about/", None)].iter() {
+		match database.add_resource(resource_name, db::ResourceIO::FORM, instance) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	for component_name in ["title", "body"].iter() {
+		match database.add_component(component_name, db::DataType::STRING) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		match database.add_component_to_model("/post/", component_name, db::DataIO::DB_BOTH) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	match database.add_component_to_model("/about/", "title", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	for component_name in ["title", "body"].iter() {
+		match database.insert("/post/", component_name, instance_id, db::Tainted::new("text".to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+
+	// deleting an instance removes its data
+	match database.delete_instance("/post/", instance_id) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.select("/post/", "title", instance_id) {
+		Err(db::DatabaseError::InstanceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// components in use are only deleted when forced
+	match database.delete_component("title", false) {
+		Err(db::DatabaseError::ComponentInUse(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.delete_component("title", true) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.get_component_data_type("title") {
+		Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	assert!(!database.describe_model("/about/").unwrap().contains_key("title"));
+
+	match database.remove_component_from_model("/post/", "body") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert!(database.describe_model("/post/").unwrap().is_empty());
+	match database.remove_component_from_model("/post/", "body") {
+		Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// with no model left using it, the component can be deleted without forcing
+	match database.delete_component("body", false) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.delete_resource("/post/") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.is_static_resource("/post/") {
+		Err(db::DatabaseError::ResourceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.delete_resource("/post/") {
+		Err(db::DatabaseError::ResourceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+}
+
+#[test]
+fn test_duplicate_names() {
+	let database = match db::Database::create(test_directory("duplicate_names")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	let instance_id = database.next_instance_id().unwrap();
+	match database.add_resource("/profile/", db::ResourceIO::FORM, Some(instance_id)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("username", db::DataType::PASSWORD) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/profile/", "username", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.insert("/profile/", "username", instance_id, db::Tainted::new("alice".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.add_resource("/profile/", db::ResourceIO::FORM, None) {
+		Err(db::DatabaseError::AlreadyDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.add_component("username", db::DataType::STRING) {
+		Err(db::DatabaseError::AlreadyDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// upserts keep the ids, so models and data survive
+	match database.upsert_component("username", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.upsert_resource("/profile/", db::ResourceIO::FORM, Some(instance_id)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.get_component_data_type("username") {
+		Err(error) => panic!("{:?}", error),
+		Ok(data_type) => assert_eq!(db::DataType::STRING, data_type)
+	};
+	match database.select("/profile/", "username", instance_id) {
+		Err(error) => panic!("{:?}", error),
+		Ok(data) => assert_eq!(db::Data::STRING("alice".to_string()), data)
+	};
+
+	// a resource with components that store data cannot become static
+	match database.add_resource("/settings/", db::ResourceIO::FORM, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/settings/", "username", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.upsert_resource("/settings/", db::ResourceIO::STATIC, None) {
+		Err(db::DatabaseError::MalformedStructure(message)) => assert!(message.contains("username")),
+		other => panic!("{:?}", other)
+	};
+	assert!(!database.is_static_resource("/settings/").unwrap());
+
+	// the data type cannot change to one the data does not fit
+	match database.upsert_component("username", db::DataType::UNSIGNED_INT_8) {
+		Err(db::DatabaseError::DataTypeMismatch(message)) => assert!(message.contains("username")),
+		other => panic!("{:?}", other)
+	};
+	assert_eq!(db::DataType::STRING, database.get_component_data_type("username").unwrap());
+
+	// upserting something new defines it
+	match database.upsert_component("email", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.get_component_data_type("email") {
+		Err(error) => panic!("{:?}", error),
+		Ok(data_type) => assert_eq!(db::DataType::STRING, data_type)
+	};
+}
+
+#[test]
+fn test_renames() {
+	let database = match db::Database::create(test_directory("renames")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	let instance_id = database.next_instance_id().unwrap();
+	match database.add_resource("/blog/username/my_first_post/", db::ResourceIO::FORM, Some(instance_id)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_resource("/blog/username/taken/", db::ResourceIO::FORM, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	for component_name in ["blogpost", "title"].iter() {
+		match database.add_component(component_name, db::DataType::STRING) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	match database.add_component_to_model("/blog/username/my_first_post/", "blogpost", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.insert("/blog/username/my_first_post/", "blogpost", instance_id, db::Tainted::new("Hello".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.rename_resource("/blog/username/my_first_post/", "/blog/username/taken/") {
+		Err(db::DatabaseError::AlreadyDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.rename_component("blogpost", "title") {
+		Err(db::DatabaseError::AlreadyDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.rename_component("missing", "other") {
+		Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	match database.rename_resource("/blog/username/my_first_post/", "/blog/username/hello/") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.rename_component("blogpost", "body") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	// the data is found under the new names
+	match database.select("/blog/username/hello/", "body", instance_id) {
+		Err(error) => panic!("{:?}", error),
+		Ok(data) => assert_eq!(db::Data::STRING("Hello".to_string()), data)
+	};
+	let model = database.load_model("/blog/username/hello/", instance_id).unwrap();
+	assert_eq!("body", model.get("body").unwrap().component_name);
+	match database.select("/blog/username/my_first_post/", "body", instance_id) {
+		Err(db::DatabaseError::ResourceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// a rename that moves a resource away from its children drops the data they inherited
+	match database.add_resource("/blog/", db::ResourceIO::FORM, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/blog/", "title", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.insert("/blog/username/hello/", "title", instance_id, db::Tainted::new("Hello".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.rename_resource("/blog/", "/articles/") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert!(!database.describe_model("/blog/username/hello/").unwrap().contains_key("title"));
+	match database.rename_resource("/articles/", "/blog/") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::NULL, database.select("/blog/username/hello/", "title", instance_id).unwrap());
+}
+
+#[test]
+fn test_introspection() {
+	let database = match db::Database::create(test_directory("introspection")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	let instance_id = database.next_instance_id().unwrap();
+	match database.add_resource("/post/", db::ResourceIO::FORM, Some(instance_id)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_resource("/login/", db::ResourceIO::STATIC, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("title", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("password", db::DataType::PASSWORD) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/post/", "title", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/login/", "password", db::DataIO::STATIC) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.list_resources() {
+		Err(error) => panic!("{:?}", error),
+		Ok(resources) => assert_eq!(vec![
+			db::ResourceInfo { resource_name: "/login/".to_string(), resource_io_type: db::ResourceIO::STATIC, instance_ids: vec![] },
+			db::ResourceInfo { resource_name: "/post/".to_string(), resource_io_type: db::ResourceIO::FORM, instance_ids: vec![instance_id] }
+		], resources)
+	};
+
+	match database.list_components() {
+		Err(error) => panic!("{:?}", error),
+		Ok(components) => assert_eq!(vec![
+			db::ComponentInfo { component_name: "password".to_string(), component_data_type: db::DataType::PASSWORD },
+			db::ComponentInfo { component_name: "title".to_string(), component_data_type: db::DataType::STRING }
+		], components)
+	};
+
+	match database.describe_model("/login/") {
+		Err(error) => panic!("{:?}", error),
+		Ok(model) => {
+			assert_eq!(1, model.len());
+			assert_eq!(Some(&db::DataIO::STATIC), model.get("password"));
+		}
+	};
+
+	match database.instances_with_data("/post/") {
+		Err(error) => panic!("{:?}", error),
+		Ok(instance_ids) => assert!(instance_ids.is_empty())
+	};
+	match database.insert("/post/", "title", instance_id, db::Tainted::new("Hello".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.instances_with_data("/post/") {
+		Err(error) => panic!("{:?}", error),
+		Ok(instance_ids) => assert_eq!(vec![instance_id], instance_ids)
+	};
+
+	// another resource with the same component does not report the data
+	match database.add_resource("/page/", db::ResourceIO::FORM, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/page/", "title", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.instances_with_data("/page/") {
+		Err(error) => panic!("{:?}", error),
+		Ok(instance_ids) => assert!(instance_ids.is_empty())
+	};
+	match database.instances_with_data("/missing/") {
+		Err(db::DatabaseError::ResourceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+}
+
+#[test]
+fn test_form_instances() {
+	let database = match db::Database::create(test_directory("form_instances")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	match database.add_resource("/blog/", db::ResourceIO::FORM, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_resource("/login/", db::ResourceIO::STATIC, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("title", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/blog/", "title", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	// every post is an instance of the same resource
+	let mut posts = Vec::new();
+	for title in ["first", "second", "third"].iter() {
+		let instance_id = match database.create_instance("/blog/") {
+			Err(error) => panic!("{:?}", error),
+			Ok(id) => id
+		};
+		match database.insert("/blog/", "title", instance_id, db::Tainted::new(title.to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		posts.push(instance_id);
+	}
+	match database.list_instances("/blog/") {
+		Err(error) => panic!("{:?}", error),
+		Ok(instance_ids) => assert_eq!(posts, instance_ids)
+	};
+	match database.load_model("/blog/", posts[1]) {
+		Err(error) => panic!("{:?}", error),
+		Ok(model) => assert_eq!(db::Data::STRING("second".to_string()), model.get("title").unwrap().data)
+	};
+
+	// ids that do not belong to the resource are rejected
+	let other_id = database.next_instance_id().unwrap();
+	assert!(!database.has_instance("/blog/", other_id).unwrap());
+	match database.load_model("/blog/", other_id) {
+		Err(db::DatabaseError::InstanceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	let model = database.load_model("/blog/", posts[0]).unwrap();
+	match database.save_model(model, "/blog/", other_id) {
+		Err(db::DatabaseError::InstanceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	match database.create_instance("/login/") {
+		Err(db::DatabaseError::MalformedStructure(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// static resources have no instances to store data for
+	match database.add_component_to_model("/login/", "title", db::DataIO::DB_BOTH) {
+		Err(db::DatabaseError::MalformedStructure(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// or to read data for, even with the id of another resource's instance
+	match database.add_component_to_model("/login/", "title", db::DataIO::STATIC) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.load_model("/login/", posts[1]) {
+		Err(error) => panic!("{:?}", error),
+		Ok(model) => assert_eq!(db::Data::NULL, model.get("title").unwrap().data)
+	};
+
+	// and do not inherit the writable components of their ancestors
+	match database.add_resource("/blog/about/", db::ResourceIO::STATIC, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	let model = database.load_model("/blog/", posts[1]).unwrap();
+	match database.save_model(model, "/blog/about/", other_id) {
+		Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// an instance belongs to one resource only
+	match database.add_resource("/page/", db::ResourceIO::FORM, Some(posts[1])) {
+		Err(db::DatabaseError::AlreadyDefined(message)) => assert!(message.contains("/blog/")),
+		other => panic!("{:?}", other)
+	};
+	match database.upsert_resource("/blog/", db::ResourceIO::FORM, Some(posts[1])) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	match database.delete_instance("/blog/", posts[0]) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert!(!database.has_instance("/blog/", posts[0]).unwrap());
+	assert_eq!(posts[1..].to_vec(), database.list_instances("/blog/").unwrap());
+}
+
+#[test]
+fn test_routes() {
+	let database = match db::Database::create(test_directory("routes")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	match database.add_resource("/blog/{username}/{slug}/", db::ResourceIO::FORM, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_resource("/blog/{username}/about/", db::ResourceIO::FORM, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_resource("/blog/admin/about/", db::ResourceIO::STATIC, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("title", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/blog/{username}/{slug}/", "title", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	// route patterns must be FORM resources with whole segment, unique parameters
+	match database.add_resource("/user/{username}/", db::ResourceIO::STATIC, None) {
+		Err(db::DatabaseError::MalformedStructure(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.add_resource("/user/{id}-{name}/", db::ResourceIO::FORM, None) {
+		Err(db::DatabaseError::MalformedStructure(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.add_resource("/user/{id}/{id}/", db::ResourceIO::FORM, None) {
+		Err(db::DatabaseError::MalformedStructure(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// the instance is only created when resolving
+	let route = match database.match_route("/blog/alice/my_first_post/") {
+		Err(error) => panic!("{:?}", error),
+		Ok(route) => route
+	};
+	assert_eq!("/blog/{username}/{slug}/", route.resource_name);
+	assert_eq!(None, route.instance_id);
+	assert_eq!("alice", route.params.get("username").unwrap());
+	assert_eq!("my_first_post", route.params.get("slug").unwrap());
+
+	let first = match database.resolve_route("/blog/alice/my_first_post/") {
+		Err(error) => panic!("{:?}", error),
+		Ok(route) => route.instance_id.unwrap()
+	};
+	let second = database.resolve_route("/blog/alice/my_second_post/").unwrap().instance_id.unwrap();
+	assert!(first != second);
+	assert_eq!(Some(first), database.resolve_route("/blog/alice/my_first_post/").unwrap().instance_id);
+	assert_eq!(vec![first, second], database.list_instances("/blog/{username}/{slug}/").unwrap());
+
+	// every post shares the model of the pattern
+	match database.insert("/blog/{username}/{slug}/", "title", second, db::Tainted::new("Second".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.load_model("/blog/{username}/{slug}/", second) {
+		Err(error) => panic!("{:?}", error),
+		Ok(model) => assert_eq!(db::Data::STRING("Second".to_string()), model.get("title").unwrap().data)
+	};
+
+	// exact names beat patterns, and patterns with more literal segments beat the rest
+	let route = database.resolve_route("/blog/admin/about/").unwrap();
+	assert_eq!("/blog/admin/about/", route.resource_name);
+	assert!(route.params.is_empty());
+	assert_eq!("/blog/{username}/about/", database.match_route("/blog/alice/about/").unwrap().resource_name);
+	match database.match_route("/blog/alice/") {
+		Err(db::DatabaseError::ResourceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// a request path spelling out a pattern is not a route to it
+	match database.resolve_route("/blog/{username}/{slug}/") {
+		Err(db::DatabaseError::MalformedData(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.match_route("/blog/alice/{slug}/") {
+		Err(db::DatabaseError::MalformedData(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	assert_eq!(vec![first, second], database.list_instances("/blog/{username}/{slug}/").unwrap());
+
+	// deleting the instance frees the path, and renaming keeps the instances
+	match database.delete_instance("/blog/{username}/{slug}/", first) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(None, database.match_route("/blog/alice/my_first_post/").unwrap().instance_id);
+	match database.rename_resource("/blog/{username}/{slug}/", "/posts/{username}/{slug}/") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(Some(second), database.match_route("/posts/alice/my_second_post/").unwrap().instance_id);
+	match database.rename_resource("/posts/{username}/{slug}/", "/posts/{slug}/") {
+		Err(db::DatabaseError::MalformedStructure(_)) => (),
+		other => panic!("{:?}", other)
+	};
+}
+
+#[test]
+fn test_hierarchy() {
+	let database = match db::Database::create(test_directory("hierarchy")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	for resource_name in ["/blog/", "/blog/{username}/", "/blog/{username}/{slug}/", "/blog/{username}/about/", "/help/"].iter() {
+		match database.add_resource(resource_name, db::ResourceIO::FORM, None) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	for component_name in ["author", "title", "body"].iter() {
+		match database.add_component(component_name, db::DataType::STRING) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	match database.add_component_to_model("/blog/", "author", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/blog/{username}/", "title", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/blog/{username}/{slug}/", "body", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+
+	// parents are derived from the longest path prefix
+	assert_eq!(Some("/blog/{username}/".to_string()), database.parent("/blog/{username}/{slug}/").unwrap());
+	assert_eq!(None, database.parent("/blog/").unwrap());
+	assert_eq!(vec!["/blog/{username}/about/".to_string(), "/blog/{username}/{slug}/".to_string()], database.children("/blog/{username}/").unwrap());
+	assert_eq!(vec!["/blog/{username}/".to_string(), "/blog/".to_string()], database.ancestors("/blog/{username}/{slug}/").unwrap());
+	assert_eq!(vec!["/blog/{username}/".to_string(), "/blog/{username}/about/".to_string(), "/blog/{username}/{slug}/".to_string()], database.descendants("/blog/").unwrap());
+
+	// children inherit the model of their ancestors and can override io types
+	match database.add_component_to_model("/blog/{username}/{slug}/", "author", db::DataIO::DB_READ_ONLY) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	let model = database.describe_model("/blog/{username}/{slug}/").unwrap();
+	assert_eq!(3, model.len());
+	assert_eq!(db::DataIO::DB_READ_ONLY, *model.get("author").unwrap());
+	assert_eq!(db::DataIO::DB_BOTH, *model.get("title").unwrap());
+	assert_eq!(db::DataIO::DB_BOTH, *database.describe_model("/blog/{username}/about/").unwrap().get("author").unwrap());
+
+	let instance_id = database.resolve_route("/blog/alice/hello/").unwrap().instance_id.unwrap();
+	match database.insert("/blog/{username}/{slug}/", "title", instance_id, db::Tainted::new("Hello".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.insert("/blog/{username}/{slug}/", "author", instance_id, db::Tainted::new("alice".to_string(), pass_filter)) {
+		Err(db::DatabaseError::ComponentNotWritable(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.load_model("/blog/{username}/{slug}/", instance_id) {
+		Err(error) => panic!("{:?}", error),
+		Ok(model) => assert_eq!(db::Data::STRING("Hello".to_string()), model.get("title").unwrap().data)
+	};
+
+	// inherited components can only be removed where they are defined
+	match database.remove_component_from_model("/blog/{username}/{slug}/", "title") {
+		Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// declared parents replace the derived ones, but cannot form cycles
+	match database.set_parent("/blog/", Some("/blog/{username}/{slug}/")) {
+		Err(db::DatabaseError::MalformedStructure(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.set_parent("/help/", Some("/blog/")) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert!(database.describe_model("/help/").unwrap().contains_key("author"));
+	let help_id = database.create_instance("/help/").unwrap();
+	match database.insert("/help/", "author", help_id, db::Tainted::new("alice".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.clear_parent("/help/") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.set_parent("/help/", Some("/blog/")) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::NULL, database.select("/help/", "author", help_id).unwrap());
+	match database.set_parent("/blog/{username}/", None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(vec!["/blog/{username}/".to_string()], database.ancestors("/blog/{username}/{slug}/").unwrap());
+	match database.clear_parent("/blog/{username}/") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(Some("/blog/".to_string()), database.parent("/blog/{username}/").unwrap());
+
+	// deleting a parent drops the data its descendants inherited from it
+	match database.delete_resource("/blog/{username}/") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(Some("/blog/".to_string()), database.parent("/blog/{username}/{slug}/").unwrap());
+	match database.select("/blog/{username}/{slug}/", "title", instance_id) {
+		Err(db::DatabaseError::ComponentNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	assert!(database.instances_with_data("/blog/{username}/{slug}/").unwrap().is_empty());
+
+	// a static page under a form resource only inherits its static components
+	match database.add_component("banner", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/blog/", "banner", db::DataIO::STATIC) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_resource("/blog/about/", db::ResourceIO::STATIC, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(Some("/blog/".to_string()), database.parent("/blog/about/").unwrap());
+	match database.load_static_model("/blog/about/") {
+		Err(error) => panic!("{:?}", error),
+		Ok(model) => {
+			assert_eq!(1, model.len());
+			assert_eq!(db::DataIO::STATIC, model.get("banner").unwrap().component_io_type);
+		}
+	};
+	match database.set_parent("/blog/about/", Some("/help/")) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(1, database.load_static_model("/blog/about/").unwrap().len());
+}
+
+#[test]
+fn test_data_types() {
+	let data = vec![
+		db::Data::BOOL(true),
+		db::Data::CHAR('a'),
+		db::Data::SIGNED_INT_8(-8),
+		db::Data::SIGNED_INT_16(-16),
+		db::Data::SIGNED_INT_32(-32),
+		db::Data::SIGNED_INT_64(-64),
+		db::Data::UNSIGNED_INT_8(8),
+		db::Data::UNSIGNED_INT_16(16),
+		db::Data::UNSIGNED_INT_32(32),
+		db::Data::UNSIGNED_INT_64(64),
+		db::Data::FLOAT_32(0.5),
+		db::Data::FLOAT_64(0.25),
+		db::Data::STRING("string".to_string())
+	];
+
+	// every data type accepts its own data and no other exact type's
+	for d in data.iter() {
+		let data_type = d.data_type().unwrap();
+		assert!(data_type.accepts(d));
+		assert!(data_type.accepts(&db::Data::NULL));
+		for other in data.iter() {
+			if other.data_type() != d.data_type() {
+				assert!(!data_type.accepts(other));
+			}
+		}
+	}
+	assert_eq!(None, db::Data::NULL.data_type());
+
+	// the untyped integer types accept any width of their sign
+	assert!(db::DataType::UINT.accepts(&db::Data::UNSIGNED_INT_8(1)));
+	assert!(db::DataType::UINT.accepts(&db::Data::UNSIGNED_INT_64(1)));
+	assert!(!db::DataType::UINT.accepts(&db::Data::SIGNED_INT_8(1)));
+	assert!(db::DataType::INT.accepts(&db::Data::SIGNED_INT_32(1)));
+	assert!(!db::DataType::INT.accepts(&db::Data::FLOAT_32(1.0)));
+	assert!(db::DataType::PASSWORD.accepts(&db::Data::STRING("secret".to_string())));
+
+	// the new types survive a reopen
+	let directory = test_directory("data_types");
+	{
+		let database = match db::Database::create(&directory) {
+			Err(error) => panic!("{:?}", error),
+			Ok(database) => database
+		};
+		match database.add_component("rating", db::DataType::FLOAT_64) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	let database = match db::Database::open(&directory) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	assert_eq!(db::DataType::FLOAT_64, database.get_component_data_type("rating").unwrap());
+}
+
+#[test]
+fn test_data_type_checks() {
+	let database = match db::Database::create(test_directory("data_type_checks")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	let instance_id = database.next_instance_id().unwrap();
+	match database.add_resource("/post/", db::ResourceIO::FORM, Some(instance_id)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("views", db::DataType::UNSIGNED_INT_32) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("title", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	for component_name in ["views", "title"].iter() {
+		match database.add_component_to_model("/post/", component_name, db::DataIO::DB_BOTH) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+
+	// missing data loads as null
+	let mut model = database.load_model("/post/", instance_id).unwrap();
+	assert_eq!(db::Data::NULL, model.get("views").unwrap().data);
+
+	// the whole model is rejected when one component has the wrong type
+	model.get_mut("views").unwrap().data = db::Data::STRING("many".to_string());
+	model.get_mut("title").unwrap().data = db::Data::STRING("Title".to_string());
+	match database.save_model(model, "/post/", instance_id) {
+		Err(db::DatabaseError::DataTypeMismatch(message)) => {
+			assert!(message.contains("views"));
+			assert!(message.contains("/post/"));
+		},
+		other => panic!("{:?}", other)
+	};
+	assert!(database.instances_with_data("/post/").unwrap().is_empty());
+
+	let mut model = database.load_model("/post/", instance_id).unwrap();
+	model.get_mut("views").unwrap().data = db::Data::UNSIGNED_INT_32(10);
+	model.get_mut("title").unwrap().data = db::Data::STRING("Title".to_string());
+	match database.save_model(model, "/post/", instance_id) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::UNSIGNED_INT_32(10), database.select("/post/", "views", instance_id).unwrap());
+
+	// the single component writes are checked too
+	match database.update("/post/", "views", instance_id, db::Tainted::new("eleven".to_string(), pass_filter)) {
+		Err(db::DatabaseError::MalformedData(message)) => assert!(message.contains("views")),
+		other => panic!("{:?}", other)
+	};
+	match database.update("/post/", "views", instance_id, db::Tainted::new(" 11 ".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::UNSIGNED_INT_32(11), database.select("/post/", "views", instance_id).unwrap());
+
+	// saving null removes the data
+	let mut model = database.load_model("/post/", instance_id).unwrap();
+	model.get_mut("title").unwrap().data = db::Data::NULL;
+	match database.save_model(model, "/post/", instance_id) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::NULL, database.select("/post/", "title", instance_id).unwrap());
+	assert_eq!(db::Data::UNSIGNED_INT_32(11), database.select("/post/", "views", instance_id).unwrap());
+
+	// components that store no data at all load as null too
+	match database.add_component("theme", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/post/", "theme", db::DataIO::STATIC) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::NULL, database.load_model("/post/", instance_id).unwrap().get("theme").unwrap().data);
+}
+
+#[test]
+fn test_parse_tainted() {
+	fn parse(value: &str, data_type: db::DataType) -> db::DBResult<db::Data> {
+		db::Tainted::new(value.to_string(), pass_filter).parse(data_type)
+	}
+
+	assert_eq!(db::Data::STRING(" spaced ".to_string()), parse(" spaced ", db::DataType::STRING).unwrap());
+	assert_eq!(db::Data::BOOL(true), parse("on", db::DataType::BOOL).unwrap());
+	assert_eq!(db::Data::BOOL(false), parse("False", db::DataType::BOOL).unwrap());
+	assert_eq!(db::Data::CHAR('x'), parse("x", db::DataType::CHAR).unwrap());
+	assert_eq!(db::Data::SIGNED_INT_8(-128), parse("-128", db::DataType::SIGNED_INT_8).unwrap());
+	assert_eq!(db::Data::SIGNED_INT_64(42), parse("+42", db::DataType::INT).unwrap());
+	assert_eq!(db::Data::UNSIGNED_INT_8(255), parse("255", db::DataType::UNSIGNED_INT_8).unwrap());
+	assert_eq!(db::Data::UNSIGNED_INT_64(0), parse("-0", db::DataType::UINT).unwrap());
+	assert_eq!(db::Data::FLOAT_32(1.5), parse("1.5", db::DataType::FLOAT_32).unwrap());
+	assert_eq!(db::Data::FLOAT_64(-2.5e10), parse("-2.5e10", db::DataType::FLOAT_64).unwrap());
+	assert_eq!(db::Data::NULL, parse("  ", db::DataType::UNSIGNED_INT_16).unwrap());
+
+	for &(value, ref data_type) in [("yes please", db::DataType::BOOL), ("xy", db::DataType::CHAR), ("12a", db::DataType::INT), ("--1", db::DataType::SIGNED_INT_32), ("1.0", db::DataType::UINT), ("NaN", db::DataType::FLOAT_64), ("inf", db::DataType::FLOAT_32)].iter() {
+		match parse(value, data_type.clone()) {
+			Err(db::DatabaseError::MalformedData(_)) => (),
+			other => panic!("{} as {:?}: {:?}", value, data_type, other)
+		};
+	}
+	for &(value, ref data_type) in [("128", db::DataType::SIGNED_INT_8), ("256", db::DataType::UNSIGNED_INT_8), ("-1", db::DataType::UNSIGNED_INT_32), ("18446744073709551616", db::DataType::UINT), ("1e39", db::DataType::FLOAT_32), ("1e309", db::DataType::FLOAT_64)].iter() {
+		match parse(value, data_type.clone()) {
+			Err(db::DatabaseError::DataOutOfRange(_)) => (),
+			other => panic!("{} as {:?}: {:?}", value, data_type, other)
+		};
+	}
+
+	// the filter runs before parsing
+	match db::Tainted::new("12".to_string(), empty_filter).parse(db::DataType::INT) {
+		Ok(db::Data::NULL) => (),
+		other => panic!("{:?}", other)
+	};
+}
+
+#[test]
+fn test_temporal_types() {
+	// dates
+	let date = db::Date::parse("2016-02-29").unwrap();
+	assert_eq!((2016, 2, 29), (date.year(), date.month(), date.day()));
+	assert_eq!("2016-02-29", date.to_string());
+	for value in ["2015-02-29", "2016-13-01", "2016-04-31"].iter() {
+		match db::Date::parse(value) {
+			Err(db::DatabaseError::DataOutOfRange(_)) => (),
+			other => panic!("{}: {:?}", value, other)
+		};
+	}
+	for value in ["2016-2-29", "20160229", "2016-02-29T00:00"].iter() {
+		match db::Date::parse(value) {
+			Err(db::DatabaseError::MalformedData(_)) => (),
+			other => panic!("{}: {:?}", value, other)
+		};
+	}
+
+	// times
+	assert_eq!(db::Time::new(13, 45, 0, 0).unwrap(), db::Time::parse("13:45").unwrap());
+	assert_eq!(db::Time::new(13, 45, 30, 250_000_000).unwrap(), db::Time::parse("13:45:30.25Z").unwrap());
+	assert_eq!("13:45:30.25", db::Time::parse("13:45:30.250").unwrap().to_string());
+	match db::Time::parse("24:00") {
+		Err(db::DatabaseError::DataOutOfRange(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// timestamps are converted to UTC
+	let epoch = db::Timestamp::parse("1970-01-01T00:00:00Z").unwrap();
+	assert_eq!(0, epoch.seconds());
+	let timestamp = db::Timestamp::parse("2015-06-30T13:45:30.5+02:00").unwrap();
+	assert_eq!(1435664730, timestamp.seconds());
+	assert_eq!(500_000_000, timestamp.nanoseconds());
+	assert_eq!("2015-06-30T11:45:30.5Z", timestamp.to_string());
+	assert_eq!(timestamp, db::Timestamp::parse("2015-06-30 06:15:30.500-0530").unwrap());
+	let before_epoch = db::Timestamp::parse("1969-12-31T23:59:59").unwrap();
+	assert_eq!(-1, before_epoch.seconds());
+	assert_eq!(db::Date::parse("1969-12-31").unwrap(), before_epoch.date());
+	assert_eq!(db::Time::new(23, 59, 59, 0).unwrap(), before_epoch.time());
+	assert_eq!("0000-01-01T00:00:00Z", db::Timestamp::parse("0000-01-01T00:00:00Z").unwrap().to_string());
+	match db::Timestamp::parse("0000-01-01T00:00:00+01:00") {
+		Err(db::DatabaseError::DataOutOfRange(_)) => (),
+		other => panic!("{:?}", other)
+	};
+
+	// ordering
+	assert!(before_epoch < epoch && epoch < timestamp);
+	assert!(db::Date::parse("2015-12-31").unwrap() < db::Date::parse("2016-01-01").unwrap());
+	assert!(db::Data::TIMESTAMP(epoch) < db::Data::TIMESTAMP(timestamp));
+
+	// temporal data is parsed from forms and persisted
+	let directory = test_directory("temporal_types");
+	let instance_id = {
+		let database = match db::Database::create(&directory) {
+			Err(error) => panic!("{:?}", error),
+			Ok(database) => database
+		};
+		match database.add_resource("/post/", db::ResourceIO::FORM, None) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		match database.add_component("published", db::DataType::TIMESTAMP) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		match database.add_component_to_model("/post/", "published", db::DataIO::DB_BOTH) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		let instance_id = database.create_instance("/post/").unwrap();
+		match database.insert("/post/", "published", instance_id, db::Tainted::new("2015-06-30T11:45:30.5Z".to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		match database.update("/post/", "published", instance_id, db::Tainted::new("yesterday".to_string(), pass_filter)) {
+			Err(db::DatabaseError::MalformedData(_)) => (),
+			other => panic!("{:?}", other)
+		};
+		instance_id
+	};
+	let database = match db::Database::open(&directory) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	assert_eq!(db::Data::TIMESTAMP(timestamp), database.select("/post/", "published", instance_id).unwrap());
+}
+
+#[test]
+fn test_blobs() {
+	let directory = test_directory("blobs");
+	let database = match db::Database::create(&directory) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	match database.add_resource("/post/", db::ResourceIO::FORM, None) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("image", db::DataType::BLOB) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/post/", "image", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	let first = database.create_instance("/post/").unwrap();
+	let second = database.create_instance("/post/").unwrap();
+
+	// larger than the copy buffer, so it is streamed in several reads
+	let image: Vec<u8> = (0..20000).map(|i| (i % 251) as u8).collect();
+	let blob = match database.write_blob(&mut &image[..]) {
+		Err(error) => panic!("{:?}", error),
+		Ok(blob) => blob
+	};
+	assert_eq!(20000, blob.len());
+	assert_eq!(64, blob.hash().len());
+
+	// identical data is stored once
+	assert_eq!(blob, database.write_blob(&mut &image[..]).unwrap());
+	let other = database.write_blob(&mut &b"other"[..]).unwrap();
+	assert_eq!(2, fs::read_dir(directory.join("blobs")).unwrap().count());
 
+	match database.merge_data("/post/", "image", first, db::Data::BLOB(blob.clone())) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	let mut model = database.load_model("/post/", second).unwrap();
+	model.get_mut("image").unwrap().data = db::Data::BLOB(blob.clone());
+	match database.save_model(model, "/post/", second) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
 
+	// the model only holds the reference, the data is streamed
+	let loaded = match database.load_model("/post/", first).unwrap().remove("image").unwrap().data {
+		db::Data::BLOB(loaded) => loaded,
+		other => panic!("{:?}", other)
+	};
+	let mut data: Vec<u8> = Vec::new();
+	database.read_blob(&loaded).unwrap().read_to_end(&mut data).unwrap();
+	assert_eq!(image, data);
+
+	// blobs cannot come from form input
+	match database.merge("/post/", "image", first, db::Tainted::new("image.png".to_string(), pass_filter)) {
+		Err(db::DatabaseError::MalformedData(_)) => (),
+		other => panic!("{:?}", other)
+	};
 
+	// only blobs nothing refers to are removed
+	assert_eq!(1, database.remove_unused_blobs().unwrap());
+	match database.read_blob(&other) {
+		Err(db::DatabaseError::FileNotFound(_)) => (),
+		other => panic!("{:?}", other)
+	};
 
+	// a removed blob cannot be saved until it is written again
+	match database.merge_data("/post/", "image", first, db::Data::BLOB(other.clone())) {
+		Err(db::DatabaseError::FileNotFound(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	assert_eq!(other, database.write_blob(&mut &b"other"[..]).unwrap());
+	match database.merge_data("/post/", "image", first, db::Data::BLOB(other.clone())) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.merge_data("/post/", "image", first, db::Data::BLOB(blob.clone())) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(1, database.remove_unused_blobs().unwrap());
+	match database.delete_instance("/post/", first) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(0, database.remove_unused_blobs().unwrap());
+	match database.delete_instance("/post/", second) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(1, database.remove_unused_blobs().unwrap());
+	assert!(database.read_blob(&blob).is_err());
+}
 
+#[test]
+fn test_collections() {
+	let database = match db::Database::create(test_directory("collections")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	let instance_id = database.next_instance_id().unwrap();
+	match database.add_resource("/post/", db::ResourceIO::FORM, Some(instance_id)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("tags", db::DataType::SET(Box::new(db::DataType::STRING))) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("ratings", db::DataType::LIST(Box::new(db::DataType::UNSIGNED_INT_8))) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("title", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	for component_name in ["tags", "ratings", "title"].iter() {
+		match database.add_component_to_model("/post/", component_name, db::DataIO::DB_BOTH) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
 
+	// a set keeps its elements sorted and unique, a list keeps every element in order
+	for tag in ["rust", "databases", "rust"].iter() {
+		match database.append_element("/post/", "tags", instance_id, db::Tainted::new(tag.to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	for rating in ["5", "3", "5"].iter() {
+		match database.append_element("/post/", "ratings", instance_id, db::Tainted::new(rating.to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	let model = database.load_model("/post/", instance_id).unwrap();
+	assert_eq!(db::Data::SET(vec![db::Data::STRING("databases".to_string()), db::Data::STRING("rust".to_string())]), model.get("tags").unwrap().data);
+	assert_eq!(db::Data::LIST(vec![db::Data::UNSIGNED_INT_8(5), db::Data::UNSIGNED_INT_8(3), db::Data::UNSIGNED_INT_8(5)]), model.get("ratings").unwrap().data);
 
+	assert!(database.contains_element("/post/", "tags", instance_id, db::Tainted::new("rust".to_string(), pass_filter)).unwrap());
+	assert!(!database.contains_element("/post/", "tags", instance_id, db::Tainted::new("web".to_string(), pass_filter)).unwrap());
 
+	// removing drops every occurrence
+	match database.remove_element("/post/", "ratings", instance_id, db::Tainted::new("5".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::LIST(vec![db::Data::UNSIGNED_INT_8(3)]), database.select("/post/", "ratings", instance_id).unwrap());
 
+	// elements are parsed into the element type and cannot be null
+	match database.append_element("/post/", "ratings", instance_id, db::Tainted::new("300".to_string(), pass_filter)) {
+		Err(db::DatabaseError::DataOutOfRange(message)) => assert!(message.contains("ratings")),
+		other => panic!("{:?}", other)
+	};
+	match database.append_element("/post/", "ratings", instance_id, db::Tainted::new(" ".to_string(), pass_filter)) {
+		Err(db::DatabaseError::MalformedData(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.append_element("/post/", "title", instance_id, db::Tainted::new("Title".to_string(), pass_filter)) {
+		Err(db::DatabaseError::DataTypeMismatch(_)) => (),
+		other => panic!("{:?}", other)
+	};
 
+	// whole collections are checked element by element and sets are normalized on save
+	let mut model = database.load_model("/post/", instance_id).unwrap();
+	model.get_mut("tags").unwrap().data = db::Data::SET(vec![db::Data::STRING("web".to_string()), db::Data::UNSIGNED_INT_8(1)]);
+	match database.save_model(model, "/post/", instance_id) {
+		Err(db::DatabaseError::DataTypeMismatch(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	let mut model = database.load_model("/post/", instance_id).unwrap();
+	model.get_mut("tags").unwrap().data = db::Data::SET(vec![db::Data::STRING("web".to_string()), db::Data::STRING("api".to_string()), db::Data::STRING("web".to_string())]);
+	match database.save_model(model, "/post/", instance_id) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::SET(vec![db::Data::STRING("api".to_string()), db::Data::STRING("web".to_string())]), database.select("/post/", "tags", instance_id).unwrap());
 
+	// integer elements are the same value whatever their width
+	match database.add_component("scores", db::DataType::SET(Box::new(db::DataType::UINT))) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/post/", "scores", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.merge_data("/post/", "scores", instance_id, db::Data::SET(vec![db::Data::UNSIGNED_INT_8(5), db::Data::UNSIGNED_INT_64(5), db::Data::UNSIGNED_INT_16(3)])) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::SET(vec![db::Data::UNSIGNED_INT_16(3), db::Data::UNSIGNED_INT_8(5)]), database.select("/post/", "scores", instance_id).unwrap());
+	assert!(database.contains_element("/post/", "scores", instance_id, db::Tainted::new("5".to_string(), pass_filter)).unwrap());
+	match database.remove_element("/post/", "scores", instance_id, db::Tainted::new("5".to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::SET(vec![db::Data::UNSIGNED_INT_16(3)]), database.select("/post/", "scores", instance_id).unwrap());
+}
 
+#[test]
+fn test_references() {
+	let database = match db::Database::create(test_directory("references")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	for resource_name in ["/post/", "/comment/", "/like/", "/review/"].iter() {
+		match database.add_resource(resource_name, db::ResourceIO::FORM, None) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	match database.add_component("post", db::DataType::REFERENCE("/nothing/".to_string(), db::OnDelete::CASCADE)) {
+		Err(db::DatabaseError::ResourceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	let components = [
+		("post", db::DataType::REFERENCE("/post/".to_string(), db::OnDelete::CASCADE), "/comment/"),
+		("liked", db::DataType::SET(Box::new(db::DataType::REFERENCE("/post/".to_string(), db::OnDelete::SET_NULL))), "/like/"),
+		("reviewed", db::DataType::REFERENCE("/comment/".to_string(), db::OnDelete::RESTRICT), "/review/")
+	];
+	for &(component_name, ref data_type, resource_name) in components.iter() {
+		match database.add_component(component_name, data_type.clone()) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		match database.add_component_to_model(resource_name, component_name, db::DataIO::DB_BOTH) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	let post_id = database.create_instance("/post/").unwrap();
+	let other_post_id = database.create_instance("/post/").unwrap();
+	let comment_id = database.create_instance("/comment/").unwrap();
+	let like_id = database.create_instance("/like/").unwrap();
+	let review_id = database.create_instance("/review/").unwrap();
+
+	// references must refer to instances of the referred resource
+	match database.update("/comment/", "post", comment_id, db::Tainted::new(comment_id.to_string(), pass_filter)) {
+		Err(db::DatabaseError::InstanceNotDefined(message)) => assert!(message.contains("/post/")),
+		other => panic!("{:?}", other)
+	};
+	match database.merge("/comment/", "post", comment_id, db::Tainted::new(post_id.to_string(), pass_filter)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::Data::REFERENCE(post_id), database.select("/comment/", "post", comment_id).unwrap());
+	for liked_id in [post_id, other_post_id].iter() {
+		match database.append_element("/like/", "liked", like_id, db::Tainted::new(liked_id.to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	match database.merge_data("/review/", "reviewed", review_id, db::Data::REFERENCE(comment_id)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
 
+	// references follow a renamed resource
+	match database.rename_resource("/post/", "/article/") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(db::DataType::REFERENCE("/article/".to_string(), db::OnDelete::CASCADE), database.get_component_data_type("post").unwrap());
 
+	// the review restricts deleting the comment, so the cascade from the post fails as a whole
+	match database.delete_instance("/article/", post_id) {
+		Err(db::DatabaseError::InstanceInUse(message)) => assert!(message.contains("reviewed")),
+		other => panic!("{:?}", other)
+	};
+	assert!(database.has_instance("/comment/", comment_id).unwrap());
+	assert!(database.contains_element("/like/", "liked", like_id, db::Tainted::new(post_id.to_string(), pass_filter)).unwrap());
 
+	match database.delete_instance("/review/", review_id) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.delete_instance("/article/", post_id) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert!(!database.has_instance("/comment/", comment_id).unwrap());
+	assert_eq!(db::Data::SET(vec![db::Data::REFERENCE(other_post_id)]), database.select("/like/", "liked", like_id).unwrap());
 
+	// a referred resource cannot be deleted
+	match database.delete_resource("/article/") {
+		Err(db::DatabaseError::ComponentInUse(message)) => assert!(message.contains("liked")),
+		other => panic!("{:?}", other)
+	};
+}
 
+#[test]
+fn test_graph_queries() {
+	let database = match db::Database::create(test_directory("graph_queries")) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	for resource_name in ["/user/", "/post/", "/comment/"].iter() {
+		match database.add_resource(resource_name, db::ResourceIO::FORM, None) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	let components = [
+		("username", db::DataType::STRING, vec!["/user/", "/post/", "/comment/"]),
+		("tags", db::DataType::SET(Box::new(db::DataType::STRING)), vec!["/post/"]),
+		("author", db::DataType::REFERENCE("/user/".to_string(), db::OnDelete::RESTRICT), vec!["/post/"]),
+		("post", db::DataType::REFERENCE("/post/".to_string(), db::OnDelete::CASCADE), vec!["/comment/"])
+	];
+	for &(component_name, ref data_type, ref resource_names) in components.iter() {
+		match database.add_component(component_name, data_type.clone()) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+		for resource_name in resource_names.iter() {
+			match database.add_component_to_model(resource_name, component_name, db::DataIO::DB_BOTH) {
+				Err(error) => panic!("{:?}", error),
+				_ => ()
+			};
+		}
+	}
 
+	assert_eq!(vec!["/comment/".to_string(), "/post/".to_string(), "/user/".to_string()], database.resources_with_component("username").unwrap());
+	assert_eq!(vec![db::SharedComponent {
+		component_name: "username".to_string(),
+		resource_names: vec!["/comment/".to_string(), "/post/".to_string(), "/user/".to_string()]
+	}], database.shared_components().unwrap());
+
+	let user_id = database.create_instance("/user/").unwrap();
+	let post_id = database.create_instance("/post/").unwrap();
+	let comment_id = database.create_instance("/comment/").unwrap();
+	let other_comment_id = database.create_instance("/comment/").unwrap();
+	let writes = [
+		("/user/", "username", user_id, db::Data::STRING("alice".to_string())),
+		("/post/", "username", post_id, db::Data::STRING("alice".to_string())),
+		("/post/", "tags", post_id, db::Data::SET(vec![db::Data::STRING("rust".to_string())])),
+		("/post/", "author", post_id, db::Data::REFERENCE(user_id)),
+		("/comment/", "username", comment_id, db::Data::STRING("bob".to_string())),
+		("/comment/", "post", comment_id, db::Data::REFERENCE(post_id)),
+		("/comment/", "username", other_comment_id, db::Data::STRING("alice".to_string())),
+		("/comment/", "post", other_comment_id, db::Data::REFERENCE(post_id))
+	];
+	for &(resource_name, component_name, instance_id, ref data) in writes.iter() {
+		match database.merge_data(resource_name, component_name, instance_id, data.clone()) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
 
+	// the same value across resources, and elements of collections
+	let alice: Vec<(String, usize)> = database.instances_with_value("username", &db::Data::STRING("alice".to_string())).unwrap().into_iter().map(|found| (found.resource_name, found.instance_id)).collect();
+	assert_eq!(vec![("/comment/".to_string(), other_comment_id), ("/post/".to_string(), post_id), ("/user/".to_string(), user_id)], alice);
+	assert_eq!(vec![db::ResourceInstance { resource_name: "/post/".to_string(), instance_id: post_id }], database.instances_with_value("tags", &db::Data::STRING("rust".to_string())).unwrap());
+
+	// references are followed either way, as deep as asked
+	let outgoing = database.traverse_references("/comment/", comment_id, db::Direction::OUTGOING, None).unwrap();
+	assert_eq!(vec![
+		db::ReachedInstance { resource_name: "/post/".to_string(), instance_id: post_id, component_name: "post".to_string(), depth: 1 },
+		db::ReachedInstance { resource_name: "/user/".to_string(), instance_id: user_id, component_name: "author".to_string(), depth: 2 }
+	], outgoing);
+	assert_eq!(1, database.traverse_references("/comment/", comment_id, db::Direction::OUTGOING, Some(1)).unwrap().len());
+
+	let incoming: Vec<(usize, usize)> = database.traverse_references("/user/", user_id, db::Direction::INCOMING, None).unwrap().into_iter().map(|reached| (reached.instance_id, reached.depth)).collect();
+	assert_eq!(vec![(post_id, 1), (comment_id, 2), (other_comment_id, 2)], incoming);
+
+	match database.traverse_references("/user/", post_id, db::Direction::INCOMING, None) {
+		Err(db::DatabaseError::InstanceNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+}
 
+#[test]
+fn test_indexes() {
+	let path = test_directory("indexes");
+	let database = match db::Database::create(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	for resource_name in ["/user/", "/post/"].iter() {
+		match database.add_resource(resource_name, db::ResourceIO::FORM, None) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	match database.add_component("username", db::DataType::STRING) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component("views", db::DataType::UNSIGNED_INT_32) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	for &(resource_name, component_name) in [("/user/", "username"), ("/post/", "username"), ("/post/", "views")].iter() {
+		match database.add_component_to_model(resource_name, component_name, db::DataIO::DB_BOTH) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	let user_id = database.create_instance("/user/").unwrap();
+	let post_ids: Vec<usize> = (0..3).map(|_| database.create_instance("/post/").unwrap()).collect();
 
+	// data stored before the index is declared is indexed too
+	match database.merge_data("/user/", "username", user_id, db::Data::STRING("alice".to_string())) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_index("username", db::IndexType::HASH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_index("username", db::IndexType::ORDERED) {
+		Err(db::DatabaseError::AlreadyDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.add_index("views", db::IndexType::ORDERED) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
 
+	// the single component writes and save_model keep the indexes up to date
+	for (post_id, views) in post_ids.iter().zip(["30", "10", "20"].iter()) {
+		match database.merge("/post/", "views", *post_id, db::Tainted::new(views.to_string(), pass_filter)) {
+			Err(error) => panic!("{:?}", error),
+			_ => ()
+		};
+	}
+	let mut model = database.load_model("/post/", post_ids[0]).unwrap();
+	model.get_mut("username").unwrap().data = db::Data::STRING("alice".to_string());
+	model.get_mut("views").unwrap().data = db::Data::UNSIGNED_INT_32(40);
+	match database.save_model(model, "/post/", post_ids[0]) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
 
+	let in_range = |database: &db::Database| -> Vec<usize> {
+		database.instances_in_range("views", Some(&db::Data::UNSIGNED_INT_32(15)), None).unwrap().into_iter().map(|found| found.instance_id).collect()
+	};
+	assert_eq!(vec![post_ids[2], post_ids[0]], in_range(&database));
+	assert_eq!(vec![post_ids[1], post_ids[2]], database.instances_in_range("views", None, Some(&db::Data::UNSIGNED_INT_32(20))).unwrap().into_iter().map(|found| found.instance_id).collect::<Vec<usize>>());
+	assert_eq!(2, database.instances_with_value("username", &db::Data::STRING("alice".to_string())).unwrap().len());
+	match database.instances_in_range("username", None, None) {
+		Err(db::DatabaseError::IndexNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
 
+	// integers of any width are found by their value, with or without an index
+	match database.add_component("likes", db::DataType::UINT) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/post/", "likes", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.merge_data("/post/", "likes", post_ids[1], db::Data::UNSIGNED_INT_8(5)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(1, database.instances_with_value("likes", &db::Data::UNSIGNED_INT_64(5)).unwrap().len());
+	match database.add_index("likes", db::IndexType::HASH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(1, database.instances_with_value("likes", &db::Data::UNSIGNED_INT_64(5)).unwrap().len());
+	assert_eq!(vec![post_ids[1], post_ids[2]], database.instances_in_range("views", Some(&db::Data::UNSIGNED_INT_64(10)), Some(&db::Data::UNSIGNED_INT_8(20))).unwrap().into_iter().map(|found| found.instance_id).collect::<Vec<usize>>());
 
+	// NaN has no place in the order of values
+	match database.add_component("rating", db::DataType::FLOAT_64) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_component_to_model("/post/", "rating", db::DataIO::DB_BOTH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.add_index("rating", db::IndexType::ORDERED) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.merge_data("/post/", "rating", post_ids[1], db::Data::FLOAT_64(::std::f64::NAN)) {
+		Err(db::DatabaseError::MalformedData(_)) => (),
+		other => panic!("{:?}", other)
+	};
+	match database.instances_in_range("rating", Some(&db::Data::FLOAT_64(::std::f64::NAN)), None) {
+		Err(db::DatabaseError::MalformedData(_)) => (),
+		other => panic!("{:?}", other)
+	};
 
+	// -0.0 is 0.0 with or without an index
+	match database.merge_data("/post/", "rating", post_ids[2], db::Data::FLOAT_64(-0.0)) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	let ordered = database.instances_with_value("rating", &db::Data::FLOAT_64(0.0)).unwrap();
+	match database.remove_index("rating") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	let unindexed = database.instances_with_value("rating", &db::Data::FLOAT_64(0.0)).unwrap();
+	assert_eq!(1, unindexed.len());
+	assert_eq!(unindexed, ordered);
+	match database.add_index("rating", db::IndexType::HASH) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(unindexed, database.instances_with_value("rating", &db::Data::FLOAT_64(0.0)).unwrap());
+	assert_eq!(unindexed, database.instances_with_value("rating", &db::Data::FLOAT_64(-0.0)).unwrap());
 
+	// deleted data leaves the indexes
+	match database.delete_instance("/post/", post_ids[0]) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	assert_eq!(vec![post_ids[2]], in_range(&database));
+	assert_eq!(vec![db::ResourceInstance { resource_name: "/user/".to_string(), instance_id: user_id }], database.instances_with_value("username", &db::Data::STRING("alice".to_string())).unwrap());
 
+	// a missing index file is rebuilt on open
+	drop(database);
+	match fs::remove_file(path.join("indexes.db")) {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	let database = match db::Database::open(&path) {
+		Err(error) => panic!("{:?}", error),
+		Ok(database) => database
+	};
+	assert!(path.join("indexes.db").exists());
+	assert_eq!(vec![post_ids[2]], in_range(&database));
 
+	match database.remove_index("views") {
+		Err(error) => panic!("{:?}", error),
+		_ => ()
+	};
+	match database.remove_index("views") {
+		Err(db::DatabaseError::IndexNotDefined(_)) => (),
+		other => panic!("{:?}", other)
+	};
+}